@@ -0,0 +1,438 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::console::StdConsole;
+use crate::graph::{Direction, DotOptions, ExportFormat, Exporter, GraphError, GraphMl, Html, Mermaid, Node, Position, Twee, ValidationMode};
+use crate::location::{Location, LocationGraph};
+use crate::transcript::{self, Recorder, Transcript};
+use crate::GraphFile;
+
+const USAGE: &str = r#"Usage:
+    text-game
+        Build and play maps using the menus.
+    text-game new <file> [--name <name>] [--description <text>] [--title <title>] [--author <author>]
+        Create a map with a single starting location and print the location's id.
+    text-game add-location <file> <name> [--description <text>]
+        Add a location that nothing leads to yet and print its id.
+    text-game connect <file> <from> <direction> <to> [--back <direction> | --both-ways]
+        Add a direction leading from one location to another, and optionally a way back.
+    text-game describe <file> <location> [--name <name>] [--description <text>] [--first-visit <text>]
+                       [--tag <tag>]... [--property <key>=<value>]... [--position <x>,<y>[,<z>]]
+                       [--sets-flag <flag>]...
+        Change a location, or print it when no changes are given. Tags and flags replace the
+        existing ones and a property with an empty value is removed. An empty position takes the
+        position away. Flags are set when the player arrives, for conditions on directions to check.
+    text-game list <file>
+        Print every location and the directions leading out of it.
+    text-game validate <file>
+        Check the map for problems.
+    text-game layout <file>
+        Give every location a position from the compass directions between them, starting from the
+        start of the map, and print anything that doesn't fit together.
+    text-game export <file> <export-file> [--highlight-root] [--highlight-current] [--collapse-both-ways]
+                     [--cluster-regions]
+        Write the map as Graphviz DOT (.dot or .gv), a Mermaid flowchart (.mmd), GraphML (.graphml),
+        a Twine story (.twee or .tw) or a web page that plays it from the start (.html), going by
+        the extension of the export file. The options only apply to DOT, where locations are put in
+        a region with a "region:<name>" tag.
+    text-game import <twee-file> <file>
+        Create a map from a Twine story written as Twee 3 and print the starting location's id.
+        Passages become locations and links become directions named after their link text.
+    text-game play <file> [--record <transcript>]
+        Play the map, reading commands from standard input. When recording, a new game is started
+        from the start of the map and everything that's read and written is saved to the transcript.
+    text-game replay <file> <transcript>
+        Play the input from a recorded transcript again and show where the output has changed.
+
+Locations can be referred to by id or by name. The exit code is 0 when the command succeeds, 1 when
+it fails and 2 when it's used incorrectly."#;
+
+const EXIT_SUCCESS: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+/// The ways that running a command can fail.
+enum CliError {
+    /// The command wasn't used correctly.
+    Usage(String),
+    /// The command couldn't do what it was asked.
+    Failed(String),
+}
+
+impl From<GraphError> for CliError {
+    fn from(e: GraphError) -> Self {
+        CliError::Failed(e.to_string())
+    }
+}
+
+/// Run the command described by the arguments, which don't include the program name. Returns the
+/// exit code.
+pub fn run(args: &[String]) -> i32 {
+    let (command, args) = match args.split_first() {
+        Some((command, args)) => (command.as_str(), args),
+        None => ("help", args),
+    };
+    let result = match command {
+        "new" => new(args),
+        "add-location" => add_location(args),
+        "connect" => connect(args),
+        "describe" => describe(args),
+        "layout" => layout(args),
+        "list" => list(args),
+        "validate" => validate(args),
+        "export" => export(args),
+        "import" => import(args),
+        "play" => play(args),
+        "replay" => replay(args),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(CliError::Usage(format!("Unknown command \"{}\".", command))),
+    };
+    match result {
+        Ok(()) => EXIT_SUCCESS,
+        Err(CliError::Usage(message)) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            EXIT_USAGE
+        }
+        Err(CliError::Failed(message)) => {
+            eprintln!("{}", message);
+            EXIT_FAILURE
+        }
+    }
+}
+
+/// The arguments for a command, split into positional arguments and `--name value` options.
+struct Arguments<'a> {
+    positional: Vec<&'a str>,
+    options: Vec<(&'a str, &'a str)>,
+    flags: Vec<&'a str>,
+}
+
+impl<'a> Arguments<'a> {
+    /// Split the arguments, accepting only the named options, which take a value, and flags, which
+    /// don't.
+    fn parse(args: &'a [String], options: &[&str], flags: &[&str]) -> Result<Self, CliError> {
+        let mut arguments = Arguments { positional: Vec::new(), options: Vec::new(), flags: Vec::new() };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let name = match arg.strip_prefix("--") {
+                Some(name) => name,
+                None => {
+                    arguments.positional.push(arg);
+                    continue;
+                }
+            };
+            if flags.contains(&name) {
+                arguments.flags.push(name);
+            } else if options.contains(&name) {
+                match args.next() {
+                    Some(value) => arguments.options.push((name, value)),
+                    None => return Err(CliError::Usage(format!("--{} needs a value.", name))),
+                }
+            } else {
+                return Err(CliError::Usage(format!("Unknown option --{}.", arg.trim_start_matches('-'))));
+            }
+        }
+        Ok(arguments)
+    }
+
+    /// The positional arguments, as long as there are exactly as many as there are names for them.
+    fn positional(&self, names: &[&str]) -> Result<&[&'a str], CliError> {
+        if self.positional.len() != names.len() {
+            return Err(CliError::Usage(format!("Expected {}.", names.iter().map(|name| format!("<{}>", name)).collect::<Vec<String>>().join(" "))));
+        }
+        Ok(&self.positional)
+    }
+
+    /// The value of the option, if it was given. When it was given more than once, the last value wins.
+    fn option(&self, name: &str) -> Option<&'a str> {
+        self.options.iter().rev().find(|(option, _)| *option == name).map(|(_, value)| *value)
+    }
+
+    /// Every value that was given for the option, in order.
+    fn all(&self, name: &str) -> Vec<&'a str> {
+        self.options.iter().filter(|(option, _)| *option == name).map(|(_, value)| *value).collect()
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.flags.contains(&name)
+    }
+}
+
+fn load_map(file_name: &str) -> Result<GraphFile, CliError> {
+    match crate::read_graph(Path::new(file_name), ValidationMode::Strict) {
+        Ok(loaded) => Ok(GraphFile::from_loaded(file_name, loaded)),
+        Err(e) => Err(CliError::Failed(format!("Couldn't load {}: {}", file_name, e))),
+    }
+}
+
+fn save_map(graph_file: &mut GraphFile) -> Result<(), CliError> {
+    crate::write_map(graph_file).map_err(|e| CliError::Failed(format!("Couldn't save {}: {}", graph_file.file_name, e)))
+}
+
+/// The id of the location that the reference is the id or name of.
+fn find_location(graph: &LocationGraph, reference: &str) -> Result<String, CliError> {
+    if graph.node(reference).is_ok() {
+        return Ok(reference.to_string());
+    }
+    let node_ids = graph.nodes()
+        .filter(|node| node.borrow().element.name.eq_ignore_ascii_case(reference))
+        .map(|node| node.borrow().id.clone())
+        .collect::<Vec<String>>();
+    match node_ids.len() {
+        0 => Err(CliError::Failed(format!("There isn't a location called \"{}\".", reference))),
+        1 => Ok(node_ids[0].clone()),
+        _ => Err(CliError::Failed(format!("More than one location is called \"{}\", so use one of their ids instead: {}",
+                                          reference, node_ids.join(", ")))),
+    }
+}
+
+fn new(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &["name", "description", "title", "author"], &[])?;
+    let file_name = arguments.positional(&["file"])?[0];
+    if Path::new(file_name).exists() {
+        return Err(CliError::Failed(format!("{} already exists.", file_name)));
+    }
+    let location = Location::new(arguments.option("name").unwrap_or("Start").to_string(),
+                                 arguments.option("description").unwrap_or_default().to_string());
+    let mut graph_file = GraphFile::new(file_name, LocationGraph::new(location));
+    let metadata = graph_file.graph.metadata_mut();
+    metadata.title = arguments.option("title").map(String::from);
+    metadata.author = arguments.option("author").map(String::from);
+    save_map(&mut graph_file)?;
+    println!("{}", crate::current_node_id(&graph_file.graph)?);
+    Ok(())
+}
+
+fn add_location(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &["description"], &[])?;
+    let positional = arguments.positional(&["file", "name"])?;
+    let mut graph_file = load_map(positional[0])?;
+    let location = Location::new(positional[1].to_string(), arguments.option("description").unwrap_or_default().to_string());
+    let node = Node::new(location);
+    let node_id = node.id.clone();
+    graph_file.graph.insert_node(Rc::new(RefCell::new(node)))?;
+    save_map(&mut graph_file)?;
+    println!("{}", node_id);
+    Ok(())
+}
+
+fn connect(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &["back"], &["both-ways"])?;
+    let positional = arguments.positional(&["file", "from", "direction", "to"])?;
+    if arguments.flag("both-ways") && arguments.option("back").is_some() {
+        return Err(CliError::Usage("Use either --back or --both-ways, not both.".to_string()));
+    }
+    let mut graph_file = load_map(positional[0])?;
+    let graph = &mut graph_file.graph;
+    let from_id = find_location(graph, positional[1])?;
+    let to_id = find_location(graph, positional[3])?;
+    let direction = normalise_direction(positional[2]);
+    if arguments.flag("both-ways") {
+        let back = graph.insert_edge_both_ways(&from_id, direction, to_id)?;
+        println!("The way back is {}.", back);
+    } else {
+        graph.insert_edge(&from_id, direction, to_id.clone())?;
+        if let Some(back) = arguments.option("back") {
+            graph.insert_edge(&to_id, normalise_direction(back), from_id)?;
+        }
+    }
+    save_map(&mut graph_file)
+}
+
+/// Built-in directions are always written out in full, the same as in the editor.
+fn normalise_direction(direction: &str) -> String {
+    direction.parse::<Direction>().map_or(direction.to_string(), |direction| direction.to_string())
+}
+
+fn describe(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &["name", "description", "first-visit", "tag", "property", "position", "sets-flag"], &[])?;
+    let positional = arguments.positional(&["file", "location"])?;
+    let mut graph_file = load_map(positional[0])?;
+    let node = graph_file.graph.node(&find_location(&graph_file.graph, positional[1])?)?;
+    if arguments.options.is_empty() {
+        let node = node.borrow();
+        let location = &node.element;
+        println!("Id: {}", node.id);
+        println!("Name: {}", location.name);
+        println!("Description: {}", location.description);
+        if let Some(first_visit_description) = &location.first_visit_description {
+            println!("First Visit Description: {}", first_visit_description);
+        }
+        println!("Tags: {}", location.tags.iter().map(String::as_str).collect::<Vec<&str>>().join(", "));
+        for (key, value) in &location.properties {
+            println!("Property: {} = {}", key, value);
+        }
+        if !location.sets_flags.is_empty() {
+            println!("Sets Flags: {}", location.sets_flags.iter().map(String::as_str).collect::<Vec<&str>>().join(", "));
+        }
+        if let Some(position) = node.position {
+            println!("Position: {}", position);
+        }
+        return Ok(());
+    }
+    {
+        let mut node = node.borrow_mut();
+        match arguments.option("position") {
+            Some("") => node.position = None,
+            Some(position) => node.position = Some(position.parse::<Position>()
+                .map_err(|_| CliError::Usage(format!("Positions are given as <x>,<y> or <x>,<y>,<z>, not \"{}\".", position)))?),
+            None => (),
+        }
+        let location = &mut node.element;
+        if let Some(name) = arguments.option("name") {
+            location.name = name.to_string();
+        }
+        if let Some(description) = arguments.option("description") {
+            location.description = description.to_string();
+        }
+        if let Some(first_visit_description) = arguments.option("first-visit") {
+            location.first_visit_description = (!first_visit_description.is_empty()).then(|| first_visit_description.to_string());
+        }
+        let tags = arguments.all("tag");
+        if !tags.is_empty() {
+            location.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        }
+        let flags = arguments.all("sets-flag");
+        if !flags.is_empty() {
+            location.sets_flags = flags.iter().map(|flag| flag.to_string()).collect();
+        }
+        for property in arguments.all("property") {
+            match property.split_once('=') {
+                Some((key, "")) => {
+                    location.properties.remove(key);
+                }
+                Some((key, value)) if !key.is_empty() => {
+                    location.properties.insert(key.to_string(), value.to_string());
+                }
+                _ => return Err(CliError::Usage(format!("Properties are given as <key>=<value>, not \"{}\".", property))),
+            }
+        }
+    }
+    save_map(&mut graph_file)
+}
+
+fn layout(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &[], &[])?;
+    let mut graph_file = load_map(arguments.positional(&["file"])?[0])?;
+    crate::lay_out_map(&mut StdConsole, &mut graph_file)?;
+    save_map(&mut graph_file)
+}
+
+fn list(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &[], &[])?;
+    let graph_file = load_map(arguments.positional(&["file"])?[0])?;
+    let graph = &graph_file.graph;
+    for node in graph.nodes() {
+        let node = node.borrow();
+        let marker = if node.id == graph.root_node_id() { " (start)" } else { "" };
+        println!("{}  {}{}", node.id, node.element.name, marker);
+        let mut edges = node.edges().collect::<Vec<(&String, &String)>>();
+        edges.sort();
+        for (direction, target_id) in edges {
+            let target_name = graph.node(target_id).map(|target| target.borrow().element.name.clone())?;
+            println!("    {} -> {}", direction, target_name);
+        }
+    }
+    Ok(())
+}
+
+fn validate(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &[], &[])?;
+    let file_name = arguments.positional(&["file"])?[0];
+    let loaded = crate::read_graph(Path::new(file_name), ValidationMode::Lenient)
+        .map_err(|e| CliError::Failed(format!("Couldn't load {}: {}", file_name, e)))?;
+    crate::print_validation_report(&mut StdConsole, &loaded.report);
+    match loaded.report.first_error() {
+        Some(_) => Err(CliError::Failed(format!("{} has errors.", file_name))),
+        None => Ok(()),
+    }
+}
+
+fn export(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &[], &["highlight-root", "highlight-current", "collapse-both-ways", "cluster-regions"])?;
+    let positional = arguments.positional(&["file", "export-file"])?;
+    let (file_name, export_file) = (positional[0], positional[1]);
+    let format = ExportFormat::for_file(export_file);
+    let exporter: Box<dyn Exporter<Location, String>> = match format {
+        Some(ExportFormat::Dot) => Box::new(DotOptions {
+            highlight_root: arguments.flag("highlight-root"),
+            highlight_current: arguments.flag("highlight-current"),
+            collapse_bidirectional: arguments.flag("collapse-both-ways"),
+            cluster_by_region: arguments.flag("cluster-regions"),
+        }),
+        Some(ExportFormat::Mermaid) => Box::new(Mermaid),
+        Some(ExportFormat::GraphMl) => Box::new(GraphMl),
+        Some(ExportFormat::Twee) => Box::new(Twee),
+        Some(ExportFormat::Html) => Box::new(Html),
+        None => return Err(CliError::Usage(format!("Don't know what format to export {} as.", export_file))),
+    };
+    let graph_file = load_map(file_name)?;
+    fs::write(export_file, exporter.export(&graph_file.graph))
+        .map_err(|e| CliError::Failed(format!("Couldn't save {}: {}", export_file, e)))?;
+    for warning in format.map(|format| crate::export_warnings(&graph_file, format)).unwrap_or_default() {
+        eprintln!("Warning: {}", warning);
+    }
+    Ok(())
+}
+
+fn import(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &[], &[])?;
+    let positional = arguments.positional(&["twee-file", "file"])?;
+    let (twee_file, file_name) = (positional[0], positional[1]);
+    if Path::new(file_name).exists() {
+        return Err(CliError::Failed(format!("{} already exists.", file_name)));
+    }
+    let twee = fs::read_to_string(twee_file)
+        .map_err(|e| CliError::Failed(format!("Couldn't read {}: {}", twee_file, e)))?;
+    let (graph, issues) = LocationGraph::from_twee(&twee)
+        .map_err(|e| CliError::Failed(format!("Couldn't import {}: {}", twee_file, e)))?;
+    for issue in issues {
+        eprintln!("Warning: {}", issue);
+    }
+    let mut graph_file = GraphFile::new(file_name, graph);
+    save_map(&mut graph_file)?;
+    println!("{}", graph_file.graph.root_node_id());
+    Ok(())
+}
+
+fn play(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &["record"], &[])?;
+    let graph_file = load_map(arguments.positional(&["file"])?[0])?;
+    let transcript_file = match arguments.option("record") {
+        Some(transcript_file) => transcript_file,
+        None => {
+            crate::interactive_mode(&mut StdConsole, &graph_file);
+            return Ok(());
+        }
+    };
+    let mut recorder = Recorder::new(StdConsole);
+    crate::play_from_start(&mut recorder, &graph_file);
+    fs::write(transcript_file, recorder.transcript().to_string())
+        .map_err(|e| CliError::Failed(format!("Couldn't save {}: {}", transcript_file, e)))
+}
+
+fn replay(args: &[String]) -> Result<(), CliError> {
+    let arguments = Arguments::parse(args, &[], &[])?;
+    let positional = arguments.positional(&["file", "transcript"])?;
+    let (file_name, transcript_file) = (positional[0], positional[1]);
+    let graph_file = load_map(file_name)?;
+    let expected = fs::read_to_string(transcript_file)
+        .map(|text| Transcript::parse(&text))
+        .map_err(|e| CliError::Failed(format!("Couldn't load {}: {}", transcript_file, e)))?;
+    let actual = transcript::replay(&expected, |console| crate::play_from_start(console, &graph_file));
+    match transcript::diff(&expected.to_string(), &actual.to_string()) {
+        Some(differences) => {
+            print!("{}", differences);
+            Err(CliError::Failed(format!("{} doesn't match what happens when it's played.", transcript_file)))
+        }
+        None => {
+            println!("{} matches.", transcript_file);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::item::{Item, ItemLocation};
+use crate::location::LocationGraph;
+use crate::save_game::SaveGame;
+
+/// Something that has to be true in a game before the player can follow a direction.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Requirement {
+    /// The player has to be carrying the item for the provided id.
+    HoldsItem { item_id: String },
+    /// The named flag has to be set.
+    FlagSet { flag: String },
+    /// The player has to have been to the location for the provided node id.
+    Visited { node_id: String },
+}
+
+impl Requirement {
+    /// Whether the requirement is met in the game.
+    pub fn is_met(&self, game: &SaveGame, items: &[Item]) -> bool {
+        match self {
+            Requirement::HoldsItem { item_id } => items.iter()
+                .any(|item| &item.id == item_id && game.item_location(item) == &ItemLocation::Inventory),
+            Requirement::FlagSet { flag } => game.flags.contains(flag),
+            Requirement::Visited { node_id } => game.visited.contains(node_id),
+        }
+    }
+
+    /// Whether the item or location that the requirement refers to has been removed from the map.
+    pub fn refers_to_missing(&self, graph: &LocationGraph, items: &[Item]) -> bool {
+        match self {
+            Requirement::HoldsItem { item_id } => !items.iter().any(|item| &item.id == item_id),
+            Requirement::FlagSet { .. } => false,
+            Requirement::Visited { node_id } => graph.node(node_id).is_err(),
+        }
+    }
+
+    /// Whether a game on the map could ever meet the requirement. Flags have to be set by arriving
+    /// at one of the locations.
+    pub fn can_be_met(&self, graph: &LocationGraph, items: &[Item]) -> bool {
+        match self {
+            Requirement::FlagSet { flag } => graph.nodes().any(|node| node.borrow().element.sets_flags.contains(flag)),
+            _ => !self.refers_to_missing(graph, items),
+        }
+    }
+}
+
+/// A requirement attached to a direction leading out of a location.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EdgeCondition {
+    /// The id of the location that the direction leads out of.
+    pub node_id: String,
+    /// The direction that the requirement applies to.
+    pub edge: String,
+    pub requirement: Requirement,
+    /// What the player is told when they try to go that way without meeting the requirement.
+    pub message: String,
+}
+
+impl EdgeCondition {
+    /// Whether the direction that the condition is on isn't in the map anymore.
+    pub fn is_on_missing_edge(&self, graph: &LocationGraph) -> bool {
+        graph.node(&self.node_id).map_or(true, |node| node.borrow().node_for_edge_element(&self.edge).is_none())
+    }
+
+    /// Whether the condition is left over from something that's been removed from the map, either
+    /// its direction or what its requirement refers to.
+    pub fn is_stale(&self, graph: &LocationGraph, items: &[Item]) -> bool {
+        self.is_on_missing_edge(graph) || self.requirement.refers_to_missing(graph, items)
+    }
+}
+
+/// The condition on the direction leading out of the location for the provided node id, if there
+/// is one.
+pub fn condition_for<'a>(conditions: &'a [EdgeCondition], node_id: &str, edge: &str) -> Option<&'a EdgeCondition> {
+    position_of(conditions, node_id, edge).map(|index| &conditions[index])
+}
+
+/// Where the condition on the direction leading out of the location for the provided node id is
+/// among the conditions, if there is one.
+pub fn position_of(conditions: &[EdgeCondition], node_id: &str, edge: &str) -> Option<usize> {
+    conditions.iter().position(|condition| condition.node_id == node_id && condition.edge == edge)
+}
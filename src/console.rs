@@ -0,0 +1,91 @@
+#[cfg(test)]
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{stdin, stdout, Write};
+
+/// Where the menus and the game read their input from and write their output to. Output is written
+/// with `write!` and `writeln!`, the same as for a `std::io::Write`.
+pub trait Console {
+    /// Read the next line of input, without the line ending, or None when there isn't any more.
+    fn read_line(&mut self) -> Option<String>;
+
+    /// Write the text exactly as it is.
+    fn write(&mut self, text: &str);
+
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) {
+        self.write(&args.to_string());
+    }
+}
+
+/// Reads from stdin and writes to stdout.
+pub struct StdConsole;
+
+impl Console for StdConsole {
+    fn read_line(&mut self) -> Option<String> {
+        let mut input = String::new();
+        if stdin().read_line(&mut input).expect("Failure getting STDIN.") == 0 {
+            return None;
+        }
+        Some(input.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    fn write(&mut self, text: &str) {
+        print!("{}", text);
+        stdout().flush().expect("Failure flushing stdout");
+    }
+}
+
+/// Plays back a fixed script of input lines and collects everything that's written, so that the
+/// menus and the game can be driven by tests.
+#[cfg(test)]
+pub struct ScriptedConsole {
+    input: VecDeque<String>,
+    output: String,
+    /// Whether running out of the script is the same as stdin being closed, rather than a mistake.
+    ends: bool,
+}
+
+#[cfg(test)]
+impl ScriptedConsole {
+    pub fn new(lines: &[&str]) -> Self {
+        ScriptedConsole {
+            input: lines.iter().map(|line| line.to_string()).collect(),
+            output: String::new(),
+            ends: false,
+        }
+    }
+
+    /// Like `new`, except that reading past the end of the script returns None the same as stdin
+    /// does once it's closed.
+    pub fn ending(lines: &[&str]) -> Self {
+        ScriptedConsole { ends: true, ..ScriptedConsole::new(lines) }
+    }
+
+    /// Everything that's been written so far.
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+}
+
+#[cfg(test)]
+impl Console for ScriptedConsole {
+    /// Panics when the script runs out, so that a test that's missing a line fails rather than
+    /// ending the menus as if the user had closed stdin. Scripts made with `ending` return None
+    /// instead.
+    fn read_line(&mut self) -> Option<String> {
+        match self.input.pop_front() {
+            Some(line) => {
+                // Echo the input so the output reads like a transcript.
+                self.output.push_str(&line);
+                self.output.push('\n');
+                Some(line)
+            }
+            None if self.ends => None,
+            None => panic!("The script ran out of input. The output so far was:\n{}", self.output),
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        self.output.push_str(text);
+    }
+}
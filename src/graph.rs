@@ -1,18 +1,45 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::fmt::Formatter;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::hash::Hash;
-use std::marker::PhantomData;
+use std::mem;
 use std::ops::Deref;
 use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use linked_hash_map::LinkedHashMap;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use serde::de::{SeqAccess, Visitor};
-use serde::ser::SerializeSeq;
+use serde::de;
+use serde::ser::{SerializeSeq, SerializeStruct};
 use serde_derive::{Deserialize, Serialize};
 use uuid::Uuid;
 
+pub use direction::{Direction, Reversible};
+pub use dot::{DotOptions, Regional};
+pub use export::{ExportFormat, Exporter, GraphMl, Mermaid};
+pub use history::{History, Operation, Undoable};
+pub use html::{Html, Playable};
+pub use layout::Position;
+pub use matching::{CaseInsensitive, EdgeMatch, EdgeMatcher, EditDistance, Exact, Fallback, UniquePrefix, edit_distance};
+pub use twee::{Passage, Twee};
+pub use validation::{ValidationIssue, ValidationMode, ValidationReport};
+
+mod analysis;
+mod direction;
+mod dot;
+mod export;
+mod history;
+mod html;
+mod layout;
+mod matching;
+mod minimap;
+mod path;
+#[cfg(test)]
+mod test_support;
+mod twee;
+mod validation;
+
 /// Represents a node in a Graph along with "pointers" to all of its edges based on their ids.
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Node<NodeElement: Serialize, EdgeElement: Hash + Eq + PartialEq + Clone> {
@@ -20,8 +47,12 @@ pub struct Node<NodeElement: Serialize, EdgeElement: Hash + Eq + PartialEq + Clo
     pub id: String,
     /// The data associated with this node.
     pub element: NodeElement,
-    /// Maps an edge to the id of the node that the edge points to.
-    edges: HashMap<EdgeElement, String>,
+    /// Maps an edge to the id of the node that the edge points to, in the order that the edges were
+    /// added.
+    edges: LinkedHashMap<EdgeElement, String>,
+    /// Where the node is, if it's been given a position.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<Position>,
 }
 
 impl<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone> Node<NodeElement, EdgeElement> {
@@ -30,30 +61,138 @@ impl<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone> Node<NodeElement, E
         Node {
             id: Uuid::new_v4().to_string(),
             element,
-            edges: HashMap::new(),
+            edges: LinkedHashMap::new(),
+            position: None,
         }
     }
 
     // Returns an Iterator over all of the edge elements for this Node.
     pub fn edge_elements(&self) -> impl Iterator<Item=EdgeElement> + '_ {
-        self.edges.iter().map(|(element, _)| element.clone())
+        self.edges.keys().cloned()
+    }
+
+    /// Returns an Iterator over all of the edges for this Node along with the ids of the Nodes that
+    /// they lead to.
+    pub fn edges(&self) -> impl Iterator<Item=(&EdgeElement, &String)> + '_ {
+        self.edges.iter()
+    }
+
+    /// Insert a new edge that leads to the Node for the provided id, returning the id of the Node
+    /// that the edge previously led to, if it already existed. An edge that's replaced keeps its
+    /// place among the others.
+    pub fn insert_edge(&mut self, element: EdgeElement, node_id: String) -> Option<String> {
+        match self.edges.get_mut(&element) {
+            Some(target_id) => Some(mem::replace(target_id, node_id)),
+            None => {
+                self.edges.insert(element, node_id);
+                None
+            }
+        }
+    }
+
+    /// Remove the edge for the provided element, returning the id of the Node that it led to, if
+    /// one existed.
+    pub fn remove_edge(&mut self, element: &EdgeElement) -> Option<String> {
+        self.edges.remove(element)
     }
 
-    /// Insert a new edge that leads to the Node for the provided id.
-    pub fn insert_edge(&mut self, element: EdgeElement, node_id: String) {
-        self.edges.insert(element.clone(), node_id);
+    /// Remove every edge from this Node that leads to the Node for the provided id.
+    pub fn remove_edges_to(&mut self, node_id: &str) {
+        let edges_to_node = self.edges.iter()
+            .filter(|(_, edge_node_id)| *edge_node_id == node_id)
+            .map(|(edge, _)| edge.clone())
+            .collect::<Vec<EdgeElement>>();
+        for edge in edges_to_node {
+            self.edges.remove(&edge);
+        }
     }
 
     /// Find the id for the Node that is connected to this Node via the provided edge, if one
     /// exists.
     pub fn node_for_edge_element(&self, element: &EdgeElement) -> Option<String> {
-        match self.edges.get(&element) {
-            Some(edge_node_id) => Some(edge_node_id.clone()),
-            None => None
+        self.edges.get(element).cloned()
+    }
+
+    /// Find the edge that the input refers to according to the matcher.
+    pub fn match_edge(&self, input: &str, matcher: &dyn EdgeMatcher<EdgeElement>) -> EdgeMatch<EdgeElement> {
+        matcher.find(input, &self.edge_elements().collect::<Vec<EdgeElement>>())
+    }
+}
+
+/// The ways that working with a Graph can fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    /// There isn't a Node in the Graph for the provided id.
+    UnknownNode(String),
+    /// The Node for the provided id doesn't have an edge for the requested element.
+    UnknownEdge(String),
+    /// A Graph needs at least one Node to act as its root.
+    EmptyGraph,
+    /// The Node for `node_id` has an edge leading to `target_id`, which isn't in the Graph.
+    DanglingEdge { node_id: String, target_id: String },
+    /// More than one Node shares the provided id.
+    DuplicateId(String),
+    /// The root Node can't be removed from the Graph.
+    RootRemoval,
+    /// An edge from the Node for the provided id doesn't have an opposite to lead back along it.
+    IrreversibleEdge(String),
+    /// More than one edge from the Node for `node_id` matches the input, so it has to be narrowed
+    /// down to one of the candidates.
+    AmbiguousEdge { node_id: String, candidates: Vec<String> },
+}
+
+impl Display for GraphError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphError::UnknownNode(node_id) => write!(f, "There isn't a node with the id {}.", node_id),
+            GraphError::UnknownEdge(node_id) => write!(f, "The node with the id {} doesn't have that edge.", node_id),
+            GraphError::EmptyGraph => write!(f, "The graph doesn't have any nodes."),
+            GraphError::DanglingEdge { node_id, target_id } =>
+                write!(f, "The node with the id {} has an edge to {}, which doesn't exist.", node_id, target_id),
+            GraphError::DuplicateId(node_id) => write!(f, "More than one node has the id {}.", node_id),
+            GraphError::RootRemoval => write!(f, "The root node can't be removed."),
+            GraphError::IrreversibleEdge(node_id) =>
+                write!(f, "The edge from the node with the id {} doesn't have an opposite.", node_id),
+            GraphError::AmbiguousEdge { node_id, candidates } =>
+                write!(f, "More than one edge from the node with the id {} matches: {}.", node_id, candidates.join(", ")),
         }
     }
 }
 
+impl Error for GraphError {}
+
+/// Descriptive information about a Graph as a whole. Timestamps are in seconds since the Unix
+/// epoch.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub created: Option<u64>,
+    pub modified: Option<u64>,
+}
+
+impl Metadata {
+    /// Create Metadata for a Graph that's being created right now.
+    pub fn new() -> Self {
+        let now = unix_timestamp();
+        Metadata {
+            title: None,
+            author: None,
+            created: Some(now),
+            modified: Some(now),
+        }
+    }
+
+    /// Record that the Graph has just been modified.
+    pub fn touch(&mut self) {
+        self.modified = Some(unix_timestamp());
+    }
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0)
+}
+
 /// Shortcut for the pointers that are used for Nodes throughout the implementation.
 type NodeRef<NodeElement, EdgeElement> = Rc<RefCell<Node<NodeElement, EdgeElement>>>;
 
@@ -62,6 +201,7 @@ type NodeRef<NodeElement, EdgeElement> = Rc<RefCell<Node<NodeElement, EdgeElemen
 pub struct Graph<NodeElement: Serialize, EdgeElement: Hash + Eq + PartialEq + Clone> {
     root_node_id: String,
     current_node_id: String,
+    metadata: Metadata,
     nodes: LinkedHashMap<String, NodeRef<NodeElement, EdgeElement>>,
 }
 
@@ -74,28 +214,164 @@ impl<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone> Graph<NodeElement,
         Graph {
             root_node_id: root_node_id.clone(),
             current_node_id: root_node_id,
+            metadata: Metadata::new(),
             nodes,
         }
     }
 
+    /// Build a Graph from previously created Nodes with an explicit root and current position,
+    /// validating it along the way. Both positions have to refer to one of the provided Nodes. In
+    /// strict mode the first error that's found is returned, while lenient mode keeps whatever it
+    /// can, drops any dangling edges and leaves the errors in the report.
+    fn from_parts(root_node_id: String, current_node_id: String, metadata: Metadata, nodes: Vec<Node<NodeElement, EdgeElement>>, mode: ValidationMode) -> Result<(Self, ValidationReport), GraphError> {
+        if nodes.is_empty() {
+            return Err(GraphError::EmptyGraph);
+        }
+        let mut report = ValidationReport::default();
+        let mut nodes_map: LinkedHashMap<String, NodeRef<NodeElement, EdgeElement>> = LinkedHashMap::with_capacity(nodes.len());
+        for node in nodes {
+            if nodes_map.contains_key(&node.id) {
+                report.push(ValidationIssue::DuplicateId(node.id));
+                continue;
+            }
+            nodes_map.insert(node.id.clone(), Rc::new(RefCell::new(node)));
+        }
+        for node_id in [&root_node_id, &current_node_id] {
+            if !nodes_map.contains_key(node_id) {
+                return Err(GraphError::UnknownNode(node_id.clone()));
+            }
+        }
+        let graph = Graph {
+            root_node_id,
+            current_node_id,
+            metadata,
+            nodes: nodes_map,
+        };
+        for issue in graph.validate().issues() {
+            report.push(issue.clone());
+        }
+        if let (ValidationMode::Strict, Some(error)) = (mode, report.first_error()) {
+            return Err(error);
+        }
+        // Dangling edges stay in the report, but they're dropped from the Graph so that saving it
+        // doesn't write them out again.
+        for node in graph.nodes.values() {
+            let dangling_edges = node.borrow().edges.iter()
+                .filter(|(_, target_id)| !graph.nodes.contains_key(*target_id))
+                .map(|(edge, _)| edge.clone())
+                .collect::<Vec<EdgeElement>>();
+            for edge in dangling_edges {
+                node.borrow_mut().remove_edge(&edge);
+            }
+        }
+        Ok((graph, report))
+    }
+
+    /// The id of the Node that the Graph starts from.
+    pub fn root_node_id(&self) -> &str {
+        &self.root_node_id
+    }
+
+    /// Descriptive information about the Graph as a whole.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Mutable access to the descriptive information about the Graph as a whole.
+    pub fn metadata_mut(&mut self) -> &mut Metadata {
+        &mut self.metadata
+    }
+
+    /// Get a reference to the node for the provided id.
+    pub fn node(&self, node_id: &str) -> Result<NodeRef<NodeElement, EdgeElement>, GraphError> {
+        match self.nodes.get(node_id) {
+            Some(node) => Ok(node.clone()),
+            None => Err(GraphError::UnknownNode(node_id.to_string())),
+        }
+    }
+
     /// Get a reference to the node for the current position in the Graph.
-    pub fn current_node(&self) -> Rc<RefCell<Node<NodeElement, EdgeElement>>> {
-        self.nodes[&self.current_node_id].clone()
+    pub fn current_node(&self) -> Result<NodeRef<NodeElement, EdgeElement>, GraphError> {
+        self.node(&self.current_node_id)
+    }
+
+    /// Add a Node to the Graph. Nothing leads to it until an edge is inserted.
+    pub fn insert_node(&mut self, node: NodeRef<NodeElement, EdgeElement>) -> Result<(), GraphError> {
+        let node_id = node.borrow().id.clone();
+        if self.nodes.contains_key(&node_id) {
+            return Err(GraphError::DuplicateId(node_id));
+        }
+        self.nodes.insert(node_id, node);
+        Ok(())
+    }
+
+    /// Insert an edge from the Node for `node_id` to the Node for `target_id`, returning the id of
+    /// the Node that the edge previously led to, if it already existed.
+    pub fn insert_edge(&mut self, node_id: &str, edge: EdgeElement, target_id: String) -> Result<Option<String>, GraphError> {
+        self.node(&target_id)?;
+        Ok(self.node(node_id)?.borrow_mut().insert_edge(edge, target_id))
+    }
+
+    /// Remove the edge for the provided element from the Node for `node_id`, returning the id of the
+    /// Node that it led to.
+    pub fn remove_edge(&mut self, node_id: &str, edge: &EdgeElement) -> Result<String, GraphError> {
+        match self.node(node_id)?.borrow_mut().remove_edge(edge) {
+            Some(target_id) => Ok(target_id),
+            None => Err(GraphError::UnknownEdge(node_id.to_string())),
+        }
+    }
+
+    /// Point an existing edge from the Node for `node_id` at the Node for `target_id`, returning the
+    /// id of the Node it previously led to.
+    pub fn retarget_edge(&mut self, node_id: &str, edge: &EdgeElement, target_id: String) -> Result<String, GraphError> {
+        self.node(&target_id)?;
+        let node = self.node(node_id)?;
+        let mut borrowed_node = node.borrow_mut();
+        match borrowed_node.node_for_edge_element(edge) {
+            Some(previous_target_id) => {
+                borrowed_node.insert_edge(edge.clone(), target_id);
+                Ok(previous_target_id)
+            }
+            None => Err(GraphError::UnknownEdge(node_id.to_string())),
+        }
     }
 
-    /// Insert an edge from the current Node that leads to a brand new Node with the provided
-    /// element.
-    pub fn insert_edge_to_new_node(&mut self, edge: EdgeElement, node: NodeElement) -> String {
-        let new_node = Node::new(node);
-        let new_node_id = new_node.id.clone();
-        self.current_node().borrow_mut().insert_edge(edge, new_node_id.clone());
-        self.nodes.insert(new_node_id.clone(), Rc::new(RefCell::new(new_node)));
-        new_node_id
+    /// Remove the Node for the provided id along with every edge in the Graph that leads to it. The
+    /// root Node can't be removed. If the removed Node was the current Node, the position in the
+    /// Graph is reset to the root.
+    pub fn remove_node(&mut self, node_id: &str) -> Result<NodeRef<NodeElement, EdgeElement>, GraphError> {
+        if node_id == self.root_node_id {
+            return Err(GraphError::RootRemoval);
+        }
+        let removed_node = match self.nodes.remove(node_id) {
+            Some(removed_node) => removed_node,
+            None => return Err(GraphError::UnknownNode(node_id.to_string())),
+        };
+        for (_, node) in &self.nodes {
+            node.borrow_mut().remove_edges_to(node_id);
+        }
+        if self.current_node_id == node_id {
+            self.reset();
+        }
+        Ok(removed_node)
     }
 
-    /// Insert an edge from the current Node to an existing node for the provided `node_id`.
-    pub fn insert_edge_to_existing_node(&mut self, edge: EdgeElement, node_id: String) {
-        self.current_node().borrow_mut().insert_edge(edge, node_id.clone());
+    /// Every edge in the Graph as the id of the Node it leads from, the edge element and the id of
+    /// the Node it leads to, in the order that the Nodes and then their edges were added.
+    fn edge_list(&self) -> Vec<(String, EdgeElement, String)> {
+        self.nodes.iter()
+            .flat_map(|(node_id, node)| {
+                node.borrow().edges()
+                    .map(|(edge, target_id)| (node_id.clone(), edge.clone(), target_id.clone()))
+                    .collect::<Vec<(String, EdgeElement, String)>>()
+            })
+            .collect()
+    }
+
+    /// The number of every Node, counting from 0 in the order they were added, keyed by the Node's
+    /// id. Formats that can't use the ids themselves number the Nodes instead.
+    fn node_numbers(&self) -> HashMap<&str, usize> {
+        self.nodes.keys().enumerate().map(|(number, node_id)| (node_id.as_str(), number)).collect()
     }
 
     /// Returns an Iterator over all the Nodes in the Graph in insertion order.
@@ -106,21 +382,17 @@ impl<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone> Graph<NodeElement,
 
     /// Update the current node in the Graph to another Node based on the provided edge element that
     /// leads to that Node.
-    pub fn traverse(&mut self, edge_element: EdgeElement) -> Option<NodeRef<NodeElement, EdgeElement>> {
-        // The first block needs to borrow the current node to try and find a match. Once the block
-        // goes out of scope, the borrow ends and the second block is responsible for assigning the
-        // matched node as the current node. Without the separate blocks, the compiler complains
-        // about self.current already being borrowed when trying to assign to it.
-        match {
-            let current_node = self.current_node();
-            let borrowed_current_node = current_node.borrow();
-            borrowed_current_node.node_for_edge_element(&edge_element)
-        } {
-            Some(matched_node) => {
-                self.current_node_id = matched_node.clone();
-                Some(self.current_node())
+    pub fn traverse(&mut self, edge_element: EdgeElement) -> Result<NodeRef<NodeElement, EdgeElement>, GraphError> {
+        // The borrow of the current node has to end before the matched node can be assigned as the
+        // current node, otherwise the compiler complains about self.current already being borrowed.
+        let matched_node_id = self.current_node()?.borrow().node_for_edge_element(&edge_element);
+        match matched_node_id {
+            Some(matched_node_id) => {
+                let matched_node = self.node(&matched_node_id)?;
+                self.current_node_id = matched_node_id;
+                Ok(matched_node)
             }
-            None => None
+            None => Err(GraphError::UnknownEdge(self.current_node_id.clone())),
         }
     }
 
@@ -130,14 +402,64 @@ impl<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone> Graph<NodeElement,
     }
 }
 
+impl<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone + Display> Graph<NodeElement, EdgeElement> {
+    /// Follow the edge from the current Node that the input refers to according to the matcher.
+    /// When more than one edge matches, the error lists them so that the user can pick one.
+    pub fn traverse_matching(&mut self, input: &str, matcher: &dyn EdgeMatcher<EdgeElement>) -> Result<NodeRef<NodeElement, EdgeElement>, GraphError> {
+        let edge_match = self.current_node()?.borrow().match_edge(input, matcher);
+        match edge_match {
+            EdgeMatch::Found(edge) => self.traverse(edge),
+            EdgeMatch::Ambiguous(edges) => {
+                let mut candidates = edges.iter().map(ToString::to_string).collect::<Vec<String>>();
+                candidates.sort();
+                Err(GraphError::AmbiguousEdge { node_id: self.current_node_id.clone(), candidates })
+            }
+            EdgeMatch::NotFound => Err(GraphError::UnknownEdge(self.current_node_id.clone())),
+        }
+    }
+}
+
+impl<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone + Reversible> Graph<NodeElement, EdgeElement> {
+    /// Link the Node for `node_id` to the Node for `target_id` along the edge and back again along
+    /// its reverse, returning the reverse edge. Nothing changes if the edge doesn't have a reverse.
+    pub fn insert_edge_both_ways(&mut self, node_id: &str, edge: EdgeElement, target_id: String) -> Result<EdgeElement, GraphError> {
+        let reverse_edge = edge.reverse().ok_or_else(|| GraphError::IrreversibleEdge(node_id.to_string()))?;
+        self.node(node_id)?;
+        self.node(&target_id)?;
+        self.insert_edge(&target_id, reverse_edge.clone(), node_id.to_string())?;
+        self.insert_edge(node_id, edge, target_id)?;
+        Ok(reverse_edge)
+    }
+}
+
+/// The version of the map file format that's written by `Serialize for Graph`. The older versions are
+/// described alongside their upgrade steps in `migration`.
+pub const FORMAT_VERSION: u32 = 3;
+
 /// JSON serialization for Graph. Derived from
 /// [an example from StackOverflow](https://stackoverflow.com/a/51284093/1060627).
 impl<NodeElement: Eq + Hash + Clone + Serialize, EdgeElement: Eq + Hash + Clone + Serialize> Serialize for Graph<NodeElement, EdgeElement> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer {
+        let mut envelope = serializer.serialize_struct("Graph", 5)?;
+        envelope.serialize_field("format_version", &FORMAT_VERSION)?;
+        envelope.serialize_field("root_node_id", &self.root_node_id)?;
+        envelope.serialize_field("current_node_id", &self.current_node_id)?;
+        envelope.serialize_field("metadata", &self.metadata)?;
+        envelope.serialize_field("nodes", &NodeSeq(&self.nodes))?;
+        envelope.end()
+    }
+}
+
+/// Serializes the nodes of a Graph as a sequence in insertion order.
+struct NodeSeq<'a, NodeElement: Serialize, EdgeElement: Eq + Hash + Clone>(&'a LinkedHashMap<String, NodeRef<NodeElement, EdgeElement>>);
+
+impl<'a, NodeElement: Serialize, EdgeElement: Eq + Hash + Clone + Serialize> Serialize for NodeSeq<'a, NodeElement, EdgeElement> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
         where S: Serializer {
         // Serialize the nodes with their ids.
-        let mut node_seq = serializer.serialize_seq(Some(self.nodes.len()))?;
-        for (_, node) in &self.nodes {
+        let mut node_seq = serializer.serialize_seq(Some(self.0.len()))?;
+        for (_, node) in self.0 {
             let borrowed_node = node.borrow();
             node_seq.serialize_element(borrowed_node.deref())?;
         }
@@ -145,54 +467,104 @@ impl<NodeElement: Eq + Hash + Clone + Serialize, EdgeElement: Eq + Hash + Clone
     }
 }
 
-struct GraphVisitor<NodeElement: Eq + Hash + Clone + Serialize, EdgeElement: Eq + Hash + Clone + Serialize> {
-    marker: PhantomData<fn() -> Graph<NodeElement, EdgeElement>>,
+/// The current map file format, which records the root and current position explicitly. The format
+/// version isn't checked here, since older formats are upgraded to it by `migration::migrate`
+/// before they're deserialized.
+#[derive(Deserialize)]
+struct GraphEnvelope<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone> {
+    root_node_id: String,
+    current_node_id: String,
+    #[serde(default)]
+    metadata: Metadata,
+    nodes: Vec<Node<NodeElement, EdgeElement>>,
 }
 
-impl<NodeElement: Eq + Hash + Clone + Serialize, EdgeElement: Eq + Hash + Clone + Serialize> GraphVisitor<NodeElement, EdgeElement> {
-    fn new() -> Self {
-        GraphVisitor {
-            marker: PhantomData
-        }
+impl<'de, NodeElement: Eq + Hash + Clone + Serialize + Deserialize<'de>, EdgeElement: Eq + Hash + Clone + Serialize + Deserialize<'de>> Graph<NodeElement, EdgeElement> {
+    /// Deserialize a Graph, validating it with the provided mode. The report lists every issue
+    /// that was found, including the ones that were tolerated in lenient mode.
+    pub fn deserialize_with_mode<D>(deserializer: D, mode: ValidationMode) -> Result<(Self, ValidationReport), D::Error> where D: Deserializer<'de> {
+        let envelope = GraphEnvelope::<NodeElement, EdgeElement>::deserialize(deserializer)?;
+        Graph::from_parts(envelope.root_node_id, envelope.current_node_id, envelope.metadata, envelope.nodes, mode)
+            .map_err(de::Error::custom)
     }
 }
 
-impl<'de, NodeElement: Eq + Hash + Clone + Serialize + Deserialize<'de>, EdgeElement: Eq + Hash + Clone + Serialize + Deserialize<'de>> Visitor<'de> for GraphVisitor<NodeElement, EdgeElement> {
-    type Value = Vec<Node<NodeElement, EdgeElement>>;
+/// Deserialization is strict, so any structural errors in the data are rejected.
+impl<'de, NodeElement: Eq + Hash + Clone + Serialize + Deserialize<'de>, EdgeElement: Eq + Hash + Clone + Serialize + Deserialize<'de>> Deserialize<'de> for Graph<NodeElement, EdgeElement> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+        Graph::deserialize_with_mode(deserializer, ValidationMode::Strict).map(|(graph, _)| graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_support::map;
 
-    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
-        formatter.write_str("A Graph structure.")
+    #[test]
+    fn removing_a_node_removes_the_edges_leading_to_it() {
+        let (mut graph, node_ids) = map(&["Hall", "Kitchen", "Cellar"], &[(0, "north", 1), (0, "down", 2), (1, "south", 0), (2, "up", 1)]);
+        let removed_node = graph.remove_node(&node_ids[1]).unwrap();
+        assert_eq!(removed_node.borrow().element, "Kitchen");
+        assert!(graph.node(&node_ids[1]).is_err());
+        assert_eq!(graph.edge_list(), vec![(node_ids[0].clone(), "down".to_string(), node_ids[2].clone())]);
     }
 
-    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error> where A: SeqAccess<'de> {
-        let mut nodes: Vec<Node<NodeElement, EdgeElement>> = Vec::with_capacity(seq.size_hint().unwrap_or(0));
-        while let Some(node) = seq.next_element::<Node<NodeElement, EdgeElement>>()? {
-            nodes.push(node);
-        }
-        Ok(nodes)
+    #[test]
+    fn the_root_node_cant_be_removed() {
+        let (mut graph, node_ids) = map(&["Hall", "Kitchen"], &[(0, "north", 1), (1, "south", 0)]);
+        assert_eq!(graph.remove_node(&node_ids[0]).err(), Some(GraphError::RootRemoval));
+        let kitchen = graph.node(&node_ids[1]).unwrap();
+        assert_eq!(graph.insert_node(kitchen), Err(GraphError::DuplicateId(node_ids[1].clone())));
+        assert_eq!(graph.nodes().count(), 2);
+        assert_eq!(graph.node(&node_ids[1]).unwrap().borrow().node_for_edge_element(&"south".to_string()), Some(node_ids[0].clone()));
     }
-}
 
-impl<'de, NodeElement: Eq + Hash + Clone + Serialize + Deserialize<'de>, EdgeElement: Eq + Hash + Clone + Serialize + Deserialize<'de>> Deserialize<'de> for Graph<NodeElement, EdgeElement> {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
-        match deserializer.deserialize_seq(GraphVisitor::new()) {
-            Ok(nodes) => {
-                // TODO: Return an error if there aren't any nodes
-                let root_node_id = {
-                    let root_node = &nodes[0];
-                    root_node.id.clone()
-                };
-                let mut nodes_map: LinkedHashMap<String, NodeRef<NodeElement, EdgeElement>> = LinkedHashMap::with_capacity(nodes.len());
-                for node in nodes {
-                    nodes_map.insert(node.id.clone(), Rc::new(RefCell::new(node)));
-                }
-                Ok(Graph {
-                    current_node_id: root_node_id.clone(),
-                    root_node_id: root_node_id.clone(),
-                    nodes: nodes_map,
-                })
-            }
-            Err(e) => Err(e),
-        }
+    #[test]
+    fn removing_the_current_node_moves_back_to_the_root() {
+        let (mut graph, node_ids) = map(&["Hall", "Kitchen", "Cellar"], &[(0, "north", 1), (0, "down", 2)]);
+        graph.traverse("down".to_string()).unwrap();
+        graph.remove_node(&node_ids[1]).unwrap();
+        assert_eq!(graph.current_node().unwrap().borrow().id, node_ids[2]);
+        graph.remove_node(&node_ids[2]).unwrap();
+        assert_eq!(graph.current_node().unwrap().borrow().id, node_ids[0]);
+    }
+
+    #[test]
+    fn edges_can_be_removed_and_retargeted() {
+        let (mut graph, node_ids) = map(&["Hall", "Kitchen", "Cellar"], &[(0, "north", 1), (0, "down", 2)]);
+        assert_eq!(graph.retarget_edge(&node_ids[0], &"north".to_string(), node_ids[2].clone()), Ok(node_ids[1].clone()));
+        assert_eq!(graph.retarget_edge(&node_ids[0], &"north".to_string(), "missing".to_string()), Err(GraphError::UnknownNode("missing".to_string())));
+        assert_eq!(graph.node(&node_ids[0]).unwrap().borrow().node_for_edge_element(&"north".to_string()), Some(node_ids[2].clone()));
+        assert_eq!(graph.remove_edge(&node_ids[0], &"down".to_string()), Ok(node_ids[2].clone()));
+        assert_eq!(graph.edge_list(), vec![(node_ids[0].clone(), "north".to_string(), node_ids[2].clone())]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn only_the_envelope_can_be_deserialized() {
+        let (graph, node_ids) = map(&["Hall", "Kitchen"], &[(0, "north", 1)]);
+        let json = serde_json::to_value(&graph).unwrap();
+        let deserialized = serde_json::from_value::<Graph<String, String>>(json.clone()).unwrap();
+        assert_eq!(deserialized.edge_list(), vec![(node_ids[0].clone(), "north".to_string(), node_ids[1].clone())]);
+
+        // A bare sequence of nodes is the first format, which only migration knows how to read.
+        assert!(serde_json::from_value::<Graph<String, String>>(json["nodes"].clone()).is_err());
+    }
+
+    #[test]
+    fn fallible_methods_say_what_was_missing() {
+        let (mut graph, node_ids) = map(&["Hall", "Kitchen"], &[(0, "north", 1)]);
+        let missing = || GraphError::UnknownNode("missing".to_string());
+        assert_eq!(graph.node("missing").err(), Some(missing()));
+        assert_eq!(graph.insert_edge(&node_ids[0], "down".to_string(), "missing".to_string()), Err(missing()));
+        assert_eq!(graph.insert_edge("missing", "up".to_string(), node_ids[0].clone()), Err(missing()));
+        assert_eq!(graph.remove_node("missing").err(), Some(missing()));
+        assert_eq!(graph.remove_node(&node_ids[0]).err(), Some(GraphError::RootRemoval));
+        let kitchen = graph.node(&node_ids[1]).unwrap();
+        assert_eq!(graph.insert_node(kitchen), Err(GraphError::DuplicateId(node_ids[1].clone())));
+        assert_eq!(graph.traverse("south".to_string()).err(), Some(GraphError::UnknownEdge(node_ids[0].clone())));
+        graph.current_node_id = "missing".to_string();
+        assert_eq!(graph.current_node().err(), Some(missing()));
+        assert_eq!(graph.traverse("north".to_string()).err(), Some(missing()));
+    }
+}
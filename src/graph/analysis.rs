@@ -0,0 +1,225 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+use serde::Serialize;
+
+use super::Graph;
+
+impl<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone> Graph<NodeElement, EdgeElement> {
+    /// The ids of every Node that can't be reached by following edges from the root, in insertion
+    /// order.
+    pub fn unreachable_nodes(&self) -> Vec<String> {
+        let reachable = self.reachable_from(&self.root_node_id);
+        self.nodes.keys()
+            .filter(|node_id| !reachable.contains(*node_id))
+            .cloned()
+            .collect()
+    }
+
+    /// The ids of every Node without any edges leading out of it, in insertion order.
+    pub fn dead_ends(&self) -> Vec<String> {
+        self.nodes.iter()
+            .filter(|(_, node)| node.borrow().edges.is_empty())
+            .map(|(node_id, _)| node_id.clone())
+            .collect()
+    }
+
+    /// The ids of every Node that the root can't be reached from, in insertion order. Once one of
+    /// these Nodes has been entered, there's no way back to the start.
+    pub fn one_way_traps(&self) -> Vec<String> {
+        // Walk the edges backwards from the root to find every Node that leads to it.
+        let mut incoming: HashMap<String, Vec<&String>> = HashMap::new();
+        for (node_id, node) in &self.nodes {
+            for (_, target_id) in node.borrow().edges.iter() {
+                incoming.entry(target_id.clone()).or_default().push(node_id);
+            }
+        }
+        let mut leads_to_root = HashSet::from([&self.root_node_id]);
+        let mut queue = VecDeque::from([&self.root_node_id]);
+        while let Some(node_id) = queue.pop_front() {
+            for source_id in incoming.get(node_id).into_iter().flatten() {
+                if leads_to_root.insert(*source_id) {
+                    queue.push_back(*source_id);
+                }
+            }
+        }
+        self.nodes.keys()
+            .filter(|node_id| !leads_to_root.contains(node_id))
+            .cloned()
+            .collect()
+    }
+
+    /// Group the Nodes into strongly connected components, where every Node in a component can be
+    /// reached from every other Node in it. Components and the ids within them are ordered by
+    /// insertion order.
+    pub fn strongly_connected_components(&self) -> Vec<Vec<String>> {
+        let order: HashMap<&String, usize> = self.nodes.keys().enumerate()
+            .map(|(idx, node_id)| (node_id, idx))
+            .collect();
+        let mut tarjan = Tarjan::default();
+        for node_id in self.nodes.keys() {
+            if !tarjan.index.contains_key(node_id) {
+                tarjan.visit(self, node_id);
+            }
+        }
+        let mut components = tarjan.components;
+        for component in &mut components {
+            component.sort_by_key(|node_id| order[node_id]);
+        }
+        components.sort_by_key(|component| order[&component[0]]);
+        components
+    }
+
+    /// The ids of every Node that can be reached by following edges from the provided Node,
+    /// including the Node itself. Edges that don't lead anywhere are ignored.
+    fn reachable_from(&self, node_id: &str) -> HashSet<String> {
+        let mut reachable = HashSet::new();
+        let mut queue = VecDeque::new();
+        if self.nodes.contains_key(node_id) {
+            reachable.insert(node_id.to_string());
+            queue.push_back(node_id.to_string());
+        }
+        while let Some(next_id) = queue.pop_front() {
+            for (_, target_id) in self.nodes[&next_id].borrow().edges.iter() {
+                if self.nodes.contains_key(target_id) && reachable.insert(target_id.clone()) {
+                    queue.push_back(target_id.clone());
+                }
+            }
+        }
+        reachable
+    }
+}
+
+/// State for [Tarjan's algorithm](https://en.wikipedia.org/wiki/Tarjan%27s_strongly_connected_components_algorithm).
+#[derive(Default)]
+struct Tarjan {
+    next_index: usize,
+    index: HashMap<String, usize>,
+    low_link: HashMap<String, usize>,
+    stack: Vec<String>,
+    on_stack: HashSet<String>,
+    components: Vec<Vec<String>>,
+}
+
+/// A Node that's being visited, along with the Nodes that it leads to and how many of them have
+/// been followed so far.
+struct Frame {
+    node_id: String,
+    target_ids: Vec<String>,
+    followed: usize,
+}
+
+impl Tarjan {
+    /// Visit the Node for `start_id` and every unvisited Node that can be reached from it. The
+    /// Nodes on the current path are kept in a Vec rather than on the call stack, so a long chain
+    /// of Nodes can't overflow it.
+    fn visit<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone>(&mut self, graph: &Graph<NodeElement, EdgeElement>, start_id: &str) {
+        let mut path = vec![self.enter(graph, start_id)];
+        while let Some(frame) = path.last_mut() {
+            if let Some(target_id) = frame.target_ids.get(frame.followed).cloned() {
+                frame.followed += 1;
+                if !self.index.contains_key(&target_id) {
+                    let target_frame = self.enter(graph, &target_id);
+                    path.push(target_frame);
+                } else if self.on_stack.contains(&target_id) {
+                    let low_link = self.low_link[&frame.node_id].min(self.index[&target_id]);
+                    self.low_link.insert(frame.node_id.clone(), low_link);
+                }
+                continue;
+            }
+
+            // Every edge has been followed, so the Node is finished with.
+            let node_id = match path.pop() {
+                Some(frame) => frame.node_id,
+                None => break,
+            };
+            if let Some(parent) = path.last() {
+                let low_link = self.low_link[&parent.node_id].min(self.low_link[&node_id]);
+                self.low_link.insert(parent.node_id.clone(), low_link);
+            }
+            if self.low_link[&node_id] == self.index[&node_id] {
+                let mut component = Vec::new();
+                while let Some(member_id) = self.stack.pop() {
+                    self.on_stack.remove(&member_id);
+                    let is_root = member_id == node_id;
+                    component.push(member_id);
+                    if is_root {
+                        break;
+                    }
+                }
+                self.components.push(component);
+            }
+        }
+    }
+
+    /// Give the Node for `node_id` its index and put it on the stack, ready for its edges to be
+    /// followed.
+    fn enter<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone>(&mut self, graph: &Graph<NodeElement, EdgeElement>, node_id: &str) -> Frame {
+        self.index.insert(node_id.to_string(), self.next_index);
+        self.low_link.insert(node_id.to_string(), self.next_index);
+        self.next_index += 1;
+        self.stack.push(node_id.to_string());
+        self.on_stack.insert(node_id.to_string());
+        let target_ids = graph.nodes[node_id].borrow().edges.values()
+            .filter(|target_id| graph.nodes.contains_key(*target_id))
+            .cloned()
+            .collect();
+        Frame { node_id: node_id.to_string(), target_ids, followed: 0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::graph::{Graph, Node};
+    use crate::graph::test_support::map;
+
+    /// A hall and kitchen that lead to each other, a cellar that only leads back to the kitchen, a
+    /// pit that can be fallen into from the cellar and an attic that nothing leads to.
+    fn house() -> (Graph<String, String>, Vec<String>) {
+        map(&["Hall", "Kitchen", "Cellar", "Pit", "Attic"], &[
+            (0, "north", 1), (1, "south", 0),
+            (1, "down", 2), (2, "up", 1),
+            (2, "jump", 3),
+            (4, "down", 0),
+        ])
+    }
+
+    #[test]
+    fn unreachable_nodes_dead_ends_and_traps_are_found() {
+        let (graph, node_ids) = house();
+        assert_eq!(graph.unreachable_nodes(), vec![node_ids[4].clone()]);
+        assert_eq!(graph.dead_ends(), vec![node_ids[3].clone()]);
+        assert_eq!(graph.one_way_traps(), vec![node_ids[3].clone()]);
+    }
+
+    #[test]
+    fn components_are_ordered_by_insertion() {
+        let (graph, node_ids) = house();
+        assert_eq!(graph.strongly_connected_components(), vec![
+            vec![node_ids[0].clone(), node_ids[1].clone(), node_ids[2].clone()],
+            vec![node_ids[3].clone()],
+            vec![node_ids[4].clone()],
+        ]);
+    }
+
+    #[test]
+    fn long_chains_do_not_overflow_the_stack() {
+        let mut graph = Graph::new("Room 0".to_string());
+        let mut previous_id = graph.root_node_id().to_string();
+        for number in 1..50_000 {
+            let node = Node::new(format!("Room {}", number));
+            let node_id = node.id.clone();
+            graph.insert_node(Rc::new(RefCell::new(node))).unwrap();
+            graph.insert_edge(&previous_id, "onwards".to_string(), node_id.clone()).unwrap();
+            previous_id = node_id;
+        }
+        let root_node_id = graph.root_node_id().to_string();
+        graph.insert_edge(&previous_id, "back to the start".to_string(), root_node_id).unwrap();
+        let components = graph.strongly_connected_components();
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].len(), 50_000);
+    }
+}
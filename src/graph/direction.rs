@@ -0,0 +1,200 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde_derive::{Deserialize, Serialize};
+
+use super::Position;
+
+/// An edge element that might have an opposite leading back the other way.
+pub trait Reversible: Sized {
+    /// The edge that leads back along this one, if there is one.
+    fn reverse(&self) -> Option<Self>;
+}
+
+/// One of the built-in directions, each of which has an opposite. They're serialized by their full
+/// names, the same way they're written in maps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    North,
+    South,
+    East,
+    West,
+    NorthEast,
+    NorthWest,
+    SouthEast,
+    SouthWest,
+    Up,
+    Down,
+    In,
+    Out,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 12] = [
+        Direction::North, Direction::South, Direction::East, Direction::West,
+        Direction::NorthEast, Direction::NorthWest, Direction::SouthEast, Direction::SouthWest,
+        Direction::Up, Direction::Down, Direction::In, Direction::Out,
+    ];
+
+    /// The direction that leads back the way this one came.
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::East => Direction::West,
+            Direction::West => Direction::East,
+            Direction::NorthEast => Direction::SouthWest,
+            Direction::NorthWest => Direction::SouthEast,
+            Direction::SouthEast => Direction::NorthWest,
+            Direction::SouthWest => Direction::NorthEast,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::In => Direction::Out,
+            Direction::Out => Direction::In,
+        }
+    }
+
+    /// The full name of the direction, which is how it's written in maps.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::North => "north",
+            Direction::South => "south",
+            Direction::East => "east",
+            Direction::West => "west",
+            Direction::NorthEast => "northeast",
+            Direction::NorthWest => "northwest",
+            Direction::SouthEast => "southeast",
+            Direction::SouthWest => "southwest",
+            Direction::Up => "up",
+            Direction::Down => "down",
+            Direction::In => "in",
+            Direction::Out => "out",
+        }
+    }
+
+    /// The short form of the direction. In and out are already short, so they don't have one.
+    pub fn abbreviation(&self) -> Option<&'static str> {
+        match self {
+            Direction::North => Some("n"),
+            Direction::South => Some("s"),
+            Direction::East => Some("e"),
+            Direction::West => Some("w"),
+            Direction::NorthEast => Some("ne"),
+            Direction::NorthWest => Some("nw"),
+            Direction::SouthEast => Some("se"),
+            Direction::SouthWest => Some("sw"),
+            Direction::Up => Some("u"),
+            Direction::Down => Some("d"),
+            Direction::In | Direction::Out => None,
+        }
+    }
+
+    /// How far a step in the direction moves. In and out don't lead anywhere in particular, so they
+    /// don't have an offset.
+    pub fn offset(&self) -> Option<Position> {
+        let (x, y, z) = match self {
+            Direction::North => (0, 1, 0),
+            Direction::South => (0, -1, 0),
+            Direction::East => (1, 0, 0),
+            Direction::West => (-1, 0, 0),
+            Direction::NorthEast => (1, 1, 0),
+            Direction::NorthWest => (-1, 1, 0),
+            Direction::SouthEast => (1, -1, 0),
+            Direction::SouthWest => (-1, -1, 0),
+            Direction::Up => (0, 0, 1),
+            Direction::Down => (0, 0, -1),
+            Direction::In | Direction::Out => return None,
+        };
+        Some(Position::new(x, y, z))
+    }
+}
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The text isn't the name or abbreviation of a built-in direction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownDirection;
+
+impl FromStr for Direction {
+    type Err = UnknownDirection;
+
+    /// Read a direction from its name or abbreviation, ignoring case. Dashes and spaces in the
+    /// diagonal names are allowed, so "north-east" and "north east" both work.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim().to_lowercase().replace(['-', ' '], "");
+        Direction::ALL.into_iter()
+            .find(|direction| direction.name() == text || direction.abbreviation() == Some(text.as_str()))
+            .ok_or(UnknownDirection)
+    }
+}
+
+impl Reversible for Direction {
+    fn reverse(&self) -> Option<Self> {
+        Some(self.opposite())
+    }
+}
+
+/// Free-form edges can be reversed when they're the name or abbreviation of a built-in direction.
+/// Custom exits like "through the mirror" don't have an opposite.
+impl Reversible for String {
+    fn reverse(&self) -> Option<Self> {
+        self.parse::<Direction>().ok().map(|direction| direction.opposite().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_direction_is_the_opposite_of_its_opposite() {
+        for direction in Direction::ALL {
+            assert_ne!(direction.opposite(), direction);
+            assert_eq!(direction.opposite().opposite(), direction);
+        }
+    }
+
+    #[test]
+    fn directions_parse_from_names_and_abbreviations() {
+        for direction in Direction::ALL {
+            assert_eq!(direction.name().parse(), Ok(direction));
+            if let Some(abbreviation) = direction.abbreviation() {
+                assert_eq!(abbreviation.to_uppercase().parse(), Ok(direction));
+            }
+        }
+        assert_eq!("North-East".parse(), Ok(Direction::NorthEast));
+        assert_eq!("south west".parse(), Ok(Direction::SouthWest));
+        assert_eq!("sideways".parse::<Direction>(), Err(UnknownDirection));
+    }
+
+    #[test]
+    fn directions_are_serialized_by_name() {
+        for direction in Direction::ALL {
+            let json = serde_json::to_string(&direction).unwrap();
+            assert_eq!(json, format!("\"{}\"", direction.name()));
+            assert_eq!(serde_json::from_str::<Direction>(&json).unwrap(), direction);
+        }
+        assert!(serde_json::from_str::<Direction>("\"n\"").is_err());
+    }
+
+    #[test]
+    fn opposite_directions_move_back_to_the_start() {
+        for direction in Direction::ALL {
+            if let (Some(offset), Some(opposite_offset)) = (direction.offset(), direction.opposite().offset()) {
+                assert_eq!(offset.moved(opposite_offset), Some(Position::default()));
+            }
+        }
+    }
+
+    #[test]
+    fn only_built_in_string_edges_are_reversible() {
+        assert_eq!("N".to_string().reverse(), Some("south".to_string()));
+        assert_eq!("in".to_string().reverse(), Some("out".to_string()));
+        assert_eq!("through the mirror".to_string().reverse(), None);
+    }
+}
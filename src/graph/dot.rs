@@ -0,0 +1,187 @@
+use std::fmt::{Display, Write};
+use std::hash::Hash;
+
+use serde::Serialize;
+
+use super::Graph;
+
+/// A node element that can belong to a region of the map, such as one floor of a house.
+pub trait Regional {
+    fn region(&self) -> Option<&str>;
+}
+
+/// What to include when exporting a Graph as Graphviz DOT.
+#[derive(Debug, Clone, Default)]
+pub struct DotOptions {
+    /// Draw the root Node with a double outline.
+    pub highlight_root: bool,
+    /// Fill in the current Node.
+    pub highlight_current: bool,
+    /// Draw a pair of edges that lead between the same two Nodes in opposite directions as a single
+    /// undirected edge labelled with both of them.
+    pub collapse_bidirectional: bool,
+    /// Group the Nodes in each region into a cluster.
+    pub cluster_by_region: bool,
+}
+
+/// Quote text for use as a DOT id or label.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+impl<NodeElement: Serialize + Display + Regional, EdgeElement: Eq + Hash + Clone + Display> Graph<NodeElement, EdgeElement> {
+    /// Describe the Graph in the Graphviz DOT language. Nodes are labelled with their elements and
+    /// edges with their edge elements, both in the order they were added, so the same Graph always
+    /// gives the same DOT.
+    pub fn to_dot(&self, options: &DotOptions) -> String {
+        let mut dot = String::from("digraph map {\n");
+        if let Some(title) = &self.metadata.title {
+            writeln!(dot, "    label={};", quote(title)).unwrap();
+        }
+
+        // Regions are listed in the order that their first Node was added.
+        let mut regions: Vec<(Option<String>, Vec<String>)> = vec![(None, Vec::new())];
+        for (node_id, node) in &self.nodes {
+            let region = match options.cluster_by_region {
+                true => node.borrow().element.region().map(String::from),
+                false => None,
+            };
+            match regions.iter_mut().find(|(existing, _)| *existing == region) {
+                Some((_, node_ids)) => node_ids.push(node_id.clone()),
+                None => regions.push((region, vec![node_id.clone()])),
+            }
+        }
+        for (cluster, (region, node_ids)) in regions.iter().enumerate() {
+            let indent = match region {
+                Some(region) => {
+                    writeln!(dot, "    subgraph cluster_{} {{", cluster).unwrap();
+                    writeln!(dot, "        label={};", quote(region)).unwrap();
+                    "        "
+                }
+                None => "    ",
+            };
+            for node_id in node_ids {
+                let mut attributes = vec![format!("label={}", quote(&self.nodes[node_id].borrow().element.to_string()))];
+                if options.highlight_root && *node_id == self.root_node_id {
+                    attributes.push("peripheries=2".to_string());
+                }
+                if options.highlight_current && *node_id == self.current_node_id {
+                    attributes.push("style=filled".to_string());
+                    attributes.push("fillcolor=lightgrey".to_string());
+                }
+                // Layout engines like neato keep pinned Nodes where they are.
+                if let Some(position) = self.nodes[node_id].borrow().position {
+                    attributes.push(format!("pos=\"{},{}!\"", position.x, position.y));
+                }
+                writeln!(dot, "{}{} [{}];", indent, quote(node_id), attributes.join(", ")).unwrap();
+            }
+            if region.is_some() {
+                writeln!(dot, "    }}").unwrap();
+            }
+        }
+
+        let edges = self.edge_list();
+        let mut drawn = vec![false; edges.len()];
+        for (idx, (from, edge, to)) in edges.iter().enumerate() {
+            if drawn[idx] {
+                continue;
+            }
+            drawn[idx] = true;
+            let way_back = match options.collapse_bidirectional {
+                true => (idx + 1..edges.len()).find(|&back_idx| !drawn[back_idx] && edges[back_idx].0 == *to && edges[back_idx].2 == *from),
+                false => None,
+            };
+            match way_back {
+                Some(back_idx) => {
+                    drawn[back_idx] = true;
+                    let label = format!("{} / {}", edge, edges[back_idx].1);
+                    writeln!(dot, "    {} -> {} [label={}, dir=none];", quote(from), quote(to), quote(&label)).unwrap();
+                }
+                None => writeln!(dot, "    {} -> {} [label={}];", quote(from), quote(to), quote(&edge.to_string())).unwrap(),
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::fmt::Formatter;
+    use std::rc::Rc;
+
+    use serde_derive::Serialize;
+
+    use super::*;
+    use crate::graph::{Node, Position};
+
+    #[derive(Serialize)]
+    struct Room(&'static str, Option<&'static str>);
+
+    impl Display for Room {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl Regional for Room {
+        fn region(&self) -> Option<&str> {
+            self.1
+        }
+    }
+
+    /// A hall at the origin with a "cellar" region below it, joined both ways, and a one-way slide back
+    /// up.
+    fn house() -> (Graph<Room, String>, String, String) {
+        let mut graph = Graph::new(Room("Hall", None));
+        let hall_id = graph.root_node_id().to_string();
+        let cellar = Node::new(Room("The \"Cellar\"", Some("below")));
+        let cellar_id = cellar.id.clone();
+        graph.insert_node(Rc::new(RefCell::new(cellar))).unwrap();
+        graph.insert_edge(&hall_id, "down".to_string(), cellar_id.clone()).unwrap();
+        graph.insert_edge(&cellar_id, "up".to_string(), hall_id.clone()).unwrap();
+        graph.insert_edge(&cellar_id, "slide".to_string(), hall_id.clone()).unwrap();
+        graph.traverse("down".to_string()).unwrap();
+        graph.node(&hall_id).unwrap().borrow_mut().position = Some(Position::new(0, 0, 0));
+        (graph, hall_id, cellar_id)
+    }
+
+    #[test]
+    fn every_node_and_edge_is_drawn_by_default() {
+        let (graph, hall_id, cellar_id) = house();
+        let expected = format!(r#"digraph map {{
+    "{hall}" [label="Hall", pos="0,0!"];
+    "{cellar}" [label="The \"Cellar\""];
+    "{hall}" -> "{cellar}" [label="down"];
+    "{cellar}" -> "{hall}" [label="up"];
+    "{cellar}" -> "{hall}" [label="slide"];
+}}
+"#, hall = hall_id, cellar = cellar_id);
+        assert_eq!(graph.to_dot(&DotOptions::default()), expected);
+    }
+
+    #[test]
+    fn options_highlight_collapse_and_cluster() {
+        let (mut graph, hall_id, cellar_id) = house();
+        graph.metadata_mut().title = Some("The House".to_string());
+        let options = DotOptions {
+            highlight_root: true,
+            highlight_current: true,
+            collapse_bidirectional: true,
+            cluster_by_region: true,
+        };
+        let expected = format!(r#"digraph map {{
+    label="The House";
+    "{hall}" [label="Hall", peripheries=2, pos="0,0!"];
+    subgraph cluster_1 {{
+        label="below";
+        "{cellar}" [label="The \"Cellar\"", style=filled, fillcolor=lightgrey];
+    }}
+    "{hall}" -> "{cellar}" [label="down / up", dir=none];
+    "{cellar}" -> "{hall}" [label="slide"];
+}}
+"#, hall = hall_id, cellar = cellar_id);
+        assert_eq!(graph.to_dot(&options), expected);
+    }
+}
@@ -0,0 +1,212 @@
+use std::fmt::{Display, Write};
+use std::hash::Hash;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::{DotOptions, Graph, Regional};
+
+/// Turns a whole Graph into text in another format. Nodes and edges are always written in the order
+/// they were added, so exporting the same Graph twice gives the same text.
+pub trait Exporter<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone> {
+    fn export(&self, graph: &Graph<NodeElement, EdgeElement>) -> String;
+}
+
+/// The formats that a Graph can be exported as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Dot,
+    Mermaid,
+    GraphMl,
+    Twee,
+    Html,
+}
+
+impl ExportFormat {
+    /// The format that's usually kept in a file with the provided name, going by its extension.
+    pub fn for_file(file_name: &str) -> Option<ExportFormat> {
+        let extension = Path::new(file_name).extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "dot" | "gv" => Some(ExportFormat::Dot),
+            "mmd" | "mermaid" => Some(ExportFormat::Mermaid),
+            "graphml" => Some(ExportFormat::GraphMl),
+            "twee" | "tw" => Some(ExportFormat::Twee),
+            "html" | "htm" => Some(ExportFormat::Html),
+            _ => None,
+        }
+    }
+}
+
+impl<NodeElement: Serialize + Display + Regional, EdgeElement: Eq + Hash + Clone + Display> Exporter<NodeElement, EdgeElement> for DotOptions {
+    fn export(&self, graph: &Graph<NodeElement, EdgeElement>) -> String {
+        graph.to_dot(self)
+    }
+}
+
+/// Exports a Mermaid flowchart that can be pasted into markdown. Mermaid is fussy about ids, so Nodes
+/// are numbered in the order they were added rather than using their own ids.
+pub struct Mermaid;
+
+impl Mermaid {
+    /// Quote text for use as a Mermaid label. Characters can't be escaped with a backslash, so
+    /// anything that Mermaid would read as markup is written as an entity instead.
+    fn quote(text: &str) -> String {
+        let text = text.replace('"', "#quot;").replace('<', "#lt;").replace('>', "#gt;");
+        format!("\"{}\"", text.replace('\n', "<br>"))
+    }
+
+    /// Quote text as a double quoted YAML string for the front matter, all on one line.
+    fn yaml_string(text: &str) -> String {
+        format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', " "))
+    }
+}
+
+impl<NodeElement: Serialize + Display, EdgeElement: Eq + Hash + Clone + Display> Exporter<NodeElement, EdgeElement> for Mermaid {
+    fn export(&self, graph: &Graph<NodeElement, EdgeElement>) -> String {
+        let mut mermaid = String::new();
+        if let Some(title) = &graph.metadata.title {
+            writeln!(mermaid, "---\ntitle: {}\n---", Mermaid::yaml_string(title)).unwrap();
+        }
+        mermaid.push_str("flowchart TD\n");
+        for (number, node) in graph.nodes.values().enumerate() {
+            writeln!(mermaid, "    n{}[{}]", number, Mermaid::quote(&node.borrow().element.to_string())).unwrap();
+        }
+        let numbers = graph.node_numbers();
+        for (from, edge, to) in graph.edge_list() {
+            // An edge to a Node that isn't in the Graph doesn't have anywhere to be drawn to.
+            if let (Some(from), Some(to)) = (numbers.get(from.as_str()), numbers.get(to.as_str())) {
+                writeln!(mermaid, "    n{} -->|{}| n{}", from, Mermaid::quote(&edge.to_string()), to).unwrap();
+            }
+        }
+        mermaid
+    }
+}
+
+/// Exports GraphML that graph tools can open. The graph has a "title" if the map has one, every Node
+/// has a "label", the root Node also has "root" set and Nodes with a position have "x", "y" and "z".
+/// Every edge has a "label".
+pub struct GraphMl;
+
+impl GraphMl {
+    /// Escape text for use in XML content or attribute values.
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+            .replace('\'', "&apos;")
+    }
+}
+
+impl<NodeElement: Serialize + Display, EdgeElement: Eq + Hash + Clone + Display> Exporter<NodeElement, EdgeElement> for GraphMl {
+    fn export(&self, graph: &Graph<NodeElement, EdgeElement>) -> String {
+        let mut graphml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="node_label" for="node" attr.name="label" attr.type="string"/>
+  <key id="root" for="node" attr.name="root" attr.type="boolean">
+    <default>false</default>
+  </key>
+  <key id="x" for="node" attr.name="x" attr.type="int"/>
+  <key id="y" for="node" attr.name="y" attr.type="int"/>
+  <key id="z" for="node" attr.name="z" attr.type="int"/>
+  <key id="edge_label" for="edge" attr.name="label" attr.type="string"/>
+  <key id="title" for="graph" attr.name="title" attr.type="string"/>
+  <graph id="map" edgedefault="directed">
+"#);
+        // Graph ids can't have spaces in them, so the title is kept as data instead.
+        if let Some(title) = &graph.metadata.title {
+            writeln!(graphml, "    <data key=\"title\">{}</data>", GraphMl::escape(title)).unwrap();
+        }
+        for (node_id, node) in &graph.nodes {
+            writeln!(graphml, "    <node id=\"{}\">", GraphMl::escape(node_id)).unwrap();
+            writeln!(graphml, "      <data key=\"node_label\">{}</data>", GraphMl::escape(&node.borrow().element.to_string())).unwrap();
+            if *node_id == graph.root_node_id {
+                graphml.push_str("      <data key=\"root\">true</data>\n");
+            }
+            if let Some(position) = node.borrow().position {
+                for (key, coordinate) in [("x", position.x), ("y", position.y), ("z", position.z)] {
+                    writeln!(graphml, "      <data key=\"{}\">{}</data>", key, coordinate).unwrap();
+                }
+            }
+            graphml.push_str("    </node>\n");
+        }
+        for (number, (from, edge, to)) in graph.edge_list().into_iter().enumerate() {
+            writeln!(graphml, "    <edge id=\"e{}\" source=\"{}\" target=\"{}\">", number, GraphMl::escape(&from), GraphMl::escape(&to)).unwrap();
+            writeln!(graphml, "      <data key=\"edge_label\">{}</data>", GraphMl::escape(&edge.to_string())).unwrap();
+            graphml.push_str("    </edge>\n");
+        }
+        graphml.push_str("  </graph>\n</graphml>\n");
+        graphml
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::Position;
+    use crate::graph::test_support::map;
+
+    /// A hall with a kitchen to the north that leads back south, plus a larder with a quote in its
+    /// name that's added last.
+    fn house() -> (Graph<String, String>, Vec<String>) {
+        map(&["Hall", "Kitchen", "Bob's \"Larder\""], &[(0, "north", 1), (1, "south", 0), (1, "in <the> pantry", 2)])
+    }
+
+    #[test]
+    fn formats_are_picked_by_extension() {
+        assert_eq!(ExportFormat::for_file("maps/house.DOT"), Some(ExportFormat::Dot));
+        assert_eq!(ExportFormat::for_file("house.mmd"), Some(ExportFormat::Mermaid));
+        assert_eq!(ExportFormat::for_file("house.graphml"), Some(ExportFormat::GraphMl));
+        assert_eq!(ExportFormat::for_file("house.twee"), Some(ExportFormat::Twee));
+        assert_eq!(ExportFormat::for_file("house.html"), Some(ExportFormat::Html));
+        assert_eq!(ExportFormat::for_file("house.json"), None);
+        assert_eq!(ExportFormat::for_file("house"), None);
+    }
+
+    #[test]
+    fn mermaid_numbers_nodes_in_the_order_they_were_added() {
+        let (mut graph, _) = house();
+        graph.metadata_mut().title = Some("The House".to_string());
+        assert_eq!(Mermaid.export(&graph), r#"---
+title: "The House"
+---
+flowchart TD
+    n0["Hall"]
+    n1["Kitchen"]
+    n2["Bob's #quot;Larder#quot;"]
+    n0 -->|"north"| n1
+    n1 -->|"south"| n0
+    n1 -->|"in #lt;the#gt; pantry"| n2
+"#);
+    }
+
+    #[test]
+    fn mermaid_quotes_the_title_and_skips_dangling_edges() {
+        let (mut graph, node_ids) = house();
+        graph.metadata_mut().title = Some("Part 1: The \"House\" \\ Garden\nAgain".to_string());
+        graph.node(&node_ids[0]).unwrap().borrow_mut().insert_edge("down".to_string(), "missing".to_string());
+        let mermaid = Mermaid.export(&graph);
+        assert!(mermaid.starts_with("---\ntitle: \"Part 1: The \\\"House\\\" \\\\ Garden Again\"\n---\n"), "{}", mermaid);
+        assert!(!mermaid.contains("down"), "{}", mermaid);
+    }
+
+    #[test]
+    fn graphml_escapes_labels_and_marks_the_title_root_and_positions() {
+        let (mut graph, node_ids) = house();
+        let graphml = GraphMl.export(&graph);
+        assert!(graphml.contains("  <graph id=\"map\" edgedefault=\"directed\">\n"), "{}", graphml);
+        let hall = format!("    <node id=\"{}\">\n      <data key=\"node_label\">Hall</data>\n      <data key=\"root\">true</data>\n    </node>\n", node_ids[0]);
+        assert!(graphml.contains(&hall), "{}", graphml);
+        assert!(graphml.contains("<data key=\"node_label\">Bob&apos;s &quot;Larder&quot;</data>"), "{}", graphml);
+        let pantry = format!("    <edge id=\"e2\" source=\"{}\" target=\"{}\">\n      <data key=\"edge_label\">in &lt;the&gt; pantry</data>\n", node_ids[1], node_ids[2]);
+        assert!(graphml.contains(&pantry), "{}", graphml);
+        assert!(!graphml.contains("<data key=\"x\">"), "{}", graphml);
+        assert!(!graphml.contains("<data key=\"title\">"), "{}", graphml);
+
+        graph.node(&node_ids[1]).unwrap().borrow_mut().position = Some(Position::new(0, 1, -1));
+        graph.metadata_mut().title = Some("The House & Garden".to_string());
+        let kitchen = GraphMl.export(&graph);
+        assert!(kitchen.contains("  <graph id=\"map\" edgedefault=\"directed\">\n    <data key=\"title\">The House &amp; Garden</data>\n"), "{}", kitchen);
+        assert!(kitchen.contains("Kitchen</data>\n      <data key=\"x\">0</data>\n      <data key=\"y\">1</data>\n      <data key=\"z\">-1</data>\n    </node>"), "{}", kitchen);
+    }
+}
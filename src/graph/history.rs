@@ -0,0 +1,347 @@
+use std::hash::Hash;
+use std::mem;
+
+use linked_hash_map::LinkedHashMap;
+use serde::Serialize;
+
+use super::{Graph, GraphError, Metadata, NodeRef, Position, Reversible};
+
+/// A reversible change to a Graph. Applying an Operation returns the Operation that undoes it.
+#[derive(Clone)]
+pub enum Operation<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone + Reversible> {
+    /// Replace the element of the Node for `node_id`.
+    SetElement { node_id: String, element: NodeElement },
+    /// Move the Node for `node_id` to the position, or take its position away.
+    SetPosition { node_id: String, position: Option<Position> },
+    /// Insert an edge from the Node for `node_id` to the Node for `target_id`, replacing any edge
+    /// for the same element.
+    AddEdge { node_id: String, edge: EdgeElement, target_id: String },
+    /// Point an existing edge from the Node for `node_id` at the Node for `target_id`.
+    RetargetEdge { node_id: String, edge: EdgeElement, target_id: String },
+    /// Insert an edge from the Node for `node_id` to the Node for `target_id` along with its
+    /// reverse leading back, replacing any edges for the same elements.
+    LinkBothWays { node_id: String, edge: EdgeElement, target_id: String },
+    /// Remove an edge from the Node for `node_id`.
+    RemoveEdge { node_id: String, edge: EdgeElement },
+    /// Put a removed edge from the Node for `node_id` back at `index` among its edges, so that
+    /// undoing a removal doesn't reorder them.
+    RestoreEdge { node_id: String, edge: EdgeElement, target_id: String, index: usize },
+    /// Add a Node to the Graph. Nothing leads to it until an edge is added.
+    AddNode { node: NodeRef<NodeElement, EdgeElement> },
+    /// Remove the Node for `node_id` along with every edge leading to it.
+    RemoveNode { node_id: String },
+    /// Put a removed Node back at `index` among the Nodes, making it the current Node again if it
+    /// was when it was removed.
+    RestoreNode { node: NodeRef<NodeElement, EdgeElement>, index: usize, current: bool },
+    /// Replace the descriptive information about the Graph as a whole.
+    SetMetadata { metadata: Metadata },
+    /// Several Operations that are applied in order and undone together.
+    Batch(Vec<Operation<NodeElement, EdgeElement>>),
+}
+
+impl<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone + Reversible> Operation<NodeElement, EdgeElement> {
+    /// Make the change to the Graph, returning the Operation that reverses it. If the change can't
+    /// be made, the Graph is left as it was.
+    pub fn apply(self, graph: &mut Graph<NodeElement, EdgeElement>) -> Result<Self, GraphError> {
+        match self {
+            Operation::SetElement { node_id, element } => {
+                let previous_element = mem::replace(&mut graph.node(&node_id)?.borrow_mut().element, element);
+                Ok(Operation::SetElement { node_id, element: previous_element })
+            }
+            Operation::SetPosition { node_id, position } => {
+                let previous_position = mem::replace(&mut graph.node(&node_id)?.borrow_mut().position, position);
+                Ok(Operation::SetPosition { node_id, position: previous_position })
+            }
+            Operation::AddEdge { node_id, edge, target_id } => {
+                let previous_target_id = graph.insert_edge(&node_id, edge.clone(), target_id)?;
+                Ok(Self::restore_edge(node_id, edge, previous_target_id))
+            }
+            Operation::RetargetEdge { node_id, edge, target_id } => {
+                let previous_target_id = graph.retarget_edge(&node_id, &edge, target_id)?;
+                Ok(Operation::RetargetEdge { node_id, edge, target_id: previous_target_id })
+            }
+            Operation::LinkBothWays { node_id, edge, target_id } => {
+                let previous_target_id = graph.node(&node_id)?.borrow().node_for_edge_element(&edge);
+                let previous_reverse_target_id = match edge.reverse() {
+                    Some(reverse_edge) => graph.node(&target_id)?.borrow().node_for_edge_element(&reverse_edge),
+                    None => None,
+                };
+                let reverse_edge = graph.insert_edge_both_ways(&node_id, edge.clone(), target_id.clone())?;
+                Ok(Operation::Batch(vec![
+                    Self::restore_edge(target_id, reverse_edge, previous_reverse_target_id),
+                    Self::restore_edge(node_id, edge, previous_target_id),
+                ]))
+            }
+            Operation::RemoveEdge { node_id, edge } => {
+                let index = graph.node(&node_id)?.borrow().edges.keys().position(|e| *e == edge).unwrap_or_default();
+                let target_id = graph.remove_edge(&node_id, &edge)?;
+                Ok(Operation::RestoreEdge { node_id, edge, target_id, index })
+            }
+            Operation::RestoreEdge { node_id, edge, target_id, index } => {
+                graph.node(&target_id)?;
+                let node = graph.node(&node_id)?;
+                let mut node = node.borrow_mut();
+                if node.edges.contains_key(&edge) {
+                    let previous_target_id = node.insert_edge(edge.clone(), target_id);
+                    return Ok(Self::restore_edge(node_id, edge, previous_target_id));
+                }
+                insert_at(&mut node.edges, index, edge.clone(), target_id);
+                Ok(Operation::RemoveEdge { node_id, edge })
+            }
+            Operation::AddNode { node } => {
+                let node_id = node.borrow().id.clone();
+                graph.insert_node(node)?;
+                Ok(Operation::RemoveNode { node_id })
+            }
+            Operation::RemoveNode { node_id } => {
+                // The edges leading to the Node are removed along with it, so they need to be
+                // remembered in order to put them back where they were.
+                let index = graph.nodes.keys().position(|id| *id == node_id)
+                    .ok_or_else(|| GraphError::UnknownNode(node_id.clone()))?;
+                let current = graph.current_node_id == node_id;
+                let incoming_edges = graph.incoming_edges(&node_id);
+                let node = graph.remove_node(&node_id)?;
+                let mut inverse = vec![Operation::RestoreNode { node, index, current }];
+                for (source_id, edge, index) in incoming_edges {
+                    inverse.push(Operation::RestoreEdge { node_id: source_id, edge, target_id: node_id.clone(), index });
+                }
+                Ok(Operation::Batch(inverse))
+            }
+            Operation::RestoreNode { node, index, current } => {
+                let node_id = node.borrow().id.clone();
+                if graph.nodes.contains_key(&node_id) {
+                    return Err(GraphError::DuplicateId(node_id));
+                }
+                insert_at(&mut graph.nodes, index, node_id.clone(), node);
+                if current {
+                    graph.current_node_id = node_id.clone();
+                }
+                Ok(Operation::RemoveNode { node_id })
+            }
+            Operation::SetMetadata { metadata } => {
+                let previous_metadata = mem::replace(&mut graph.metadata, metadata);
+                Ok(Operation::SetMetadata { metadata: previous_metadata })
+            }
+            Operation::Batch(operations) => {
+                let mut inverse = Vec::with_capacity(operations.len());
+                for operation in operations {
+                    match operation.apply(graph) {
+                        Ok(inverse_operation) => inverse.push(inverse_operation),
+                        Err(e) => {
+                            // Roll back whatever was already applied so the Batch is all or nothing.
+                            while let Some(inverse_operation) = inverse.pop() {
+                                let _ = inverse_operation.apply(graph);
+                            }
+                            return Err(e);
+                        }
+                    }
+                }
+                inverse.reverse();
+                Ok(Operation::Batch(inverse))
+            }
+        }
+    }
+
+    /// The Operation that puts an edge back the way it was before it was inserted, given the id of
+    /// the Node that it previously led to.
+    fn restore_edge(node_id: String, edge: EdgeElement, previous_target_id: Option<String>) -> Self {
+        match previous_target_id {
+            Some(previous_target_id) => Operation::AddEdge { node_id, edge, target_id: previous_target_id },
+            None => Operation::RemoveEdge { node_id, edge },
+        }
+    }
+}
+
+/// Insert an entry that isn't already in the map so that it ends up at `index`, with the entries
+/// from there on following it.
+fn insert_at<K: Hash + Eq + Clone, V>(map: &mut LinkedHashMap<K, V>, index: usize, key: K, value: V) {
+    let following_keys = map.keys().skip(index).cloned().collect::<Vec<K>>();
+    map.insert(key, value);
+    for following_key in following_keys {
+        map.get_refresh(&following_key);
+    }
+}
+
+/// A change that can be made to a target and returns the change that reverses it, so that it can be
+/// kept in a History. If the change can't be made, the target is left as it was.
+pub trait Undoable<Target>: Clone + Sized {
+    fn apply(self, target: &mut Target) -> Result<Self, GraphError>;
+}
+
+impl<NodeElement: Serialize + Clone, EdgeElement: Eq + Hash + Clone + Reversible> Undoable<Graph<NodeElement, EdgeElement>> for Operation<NodeElement, EdgeElement> {
+    fn apply(self, graph: &mut Graph<NodeElement, EdgeElement>) -> Result<Self, GraphError> {
+        Operation::apply(self, graph)
+    }
+}
+
+/// Undo and redo stacks of changes that have been applied to a target, which is usually a Graph.
+/// Every change needs to go through the History for undo and redo to stay consistent with the
+/// target.
+pub struct History<Change> {
+    undo_stack: Vec<Change>,
+    redo_stack: Vec<Change>,
+}
+
+impl<Change> History<Change> {
+    pub fn new() -> Self {
+        History {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        }
+    }
+
+    /// Apply the change to the target and remember how to undo it. Anything that was undone can no
+    /// longer be redone.
+    pub fn apply<Target>(&mut self, target: &mut Target, change: Change) -> Result<(), GraphError> where Change: Undoable<Target> {
+        let inverse = change.apply(target)?;
+        self.undo_stack.push(inverse);
+        self.redo_stack.clear();
+        Ok(())
+    }
+
+    /// Reverse the most recently applied change. Returns whether there was anything to undo.
+    pub fn undo<Target>(&mut self, target: &mut Target) -> Result<bool, GraphError> where Change: Undoable<Target> {
+        Self::transfer(target, &mut self.undo_stack, &mut self.redo_stack)
+    }
+
+    /// Reapply the most recently undone change. Returns whether there was anything to redo.
+    pub fn redo<Target>(&mut self, target: &mut Target) -> Result<bool, GraphError> where Change: Undoable<Target> {
+        Self::transfer(target, &mut self.redo_stack, &mut self.undo_stack)
+    }
+
+    /// Apply the change from the top of one stack and push its inverse onto the other. If the
+    /// change fails, it's left where it was.
+    fn transfer<Target>(target: &mut Target, from: &mut Vec<Change>, to: &mut Vec<Change>) -> Result<bool, GraphError> where Change: Undoable<Target> {
+        let change = match from.pop() {
+            Some(change) => change,
+            None => return Ok(false),
+        };
+        match change.clone().apply(target) {
+            Ok(inverse) => {
+                to.push(inverse);
+                Ok(true)
+            }
+            Err(e) => {
+                from.push(change);
+                Err(e)
+            }
+        }
+    }
+}
+
+impl<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone> Graph<NodeElement, EdgeElement> {
+    /// Every edge in the Graph that leads to the Node for `node_id` from another Node, along with
+    /// the source Node's id and the edge's index among the source Node's edges.
+    fn incoming_edges(&self, node_id: &str) -> Vec<(String, EdgeElement, usize)> {
+        let mut incoming_edges = Vec::new();
+        for (source_id, node) in &self.nodes {
+            if source_id == node_id {
+                continue;
+            }
+            for (index, (edge, target_id)) in node.borrow().edges.iter().enumerate() {
+                if target_id == node_id {
+                    incoming_edges.push((source_id.clone(), edge.clone(), index));
+                }
+            }
+        }
+        incoming_edges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::graph::Node;
+    use crate::graph::test_support::map;
+
+    /// Everything about the Graph that an Operation can change, in the order it's kept: each Node's
+    /// id, element, position and edges, followed by the id of the current Node and the title.
+    type Snapshot = (Vec<(String, String, Option<Position>, Vec<(String, String)>)>, String, Option<String>);
+
+    fn snapshot(graph: &Graph<String, String>) -> Snapshot {
+        let nodes = graph.nodes.values()
+            .map(|node| {
+                let node = node.borrow();
+                let edges = node.edges().map(|(edge, target_id)| (edge.clone(), target_id.clone())).collect();
+                (node.id.clone(), node.element.clone(), node.position, edges)
+            })
+            .collect();
+        (nodes, graph.current_node_id.clone(), graph.metadata.title.clone())
+    }
+
+    /// A hall leading north to a kitchen and up to a study, with the kitchen leading east to a
+    /// pantry and back south, and the pantry leading back west.
+    fn house() -> (Graph<String, String>, Vec<String>) {
+        map(&["Hall", "Kitchen", "Study", "Pantry"], &[
+            (0, "north", 1), (0, "up", 2), (1, "east", 3), (1, "south", 0), (3, "west", 1), (2, "down", 1),
+        ])
+    }
+
+    /// Apply the Operation through a History, then check that undoing it puts the Graph back the way
+    /// it was and redoing it makes the same change again.
+    fn assert_round_trip(graph: &mut Graph<String, String>, operation: Operation<String, String>) {
+        let mut history = History::new();
+        let before = snapshot(graph);
+        history.apply(graph, operation).unwrap();
+        let after = snapshot(graph);
+        assert_ne!(before, after);
+
+        assert!(history.undo(graph).unwrap());
+        assert_eq!(before, snapshot(graph));
+        assert!(history.redo(graph).unwrap());
+        assert_eq!(after, snapshot(graph));
+        assert!(history.undo(graph).unwrap());
+        assert_eq!(before, snapshot(graph));
+        assert!(!history.undo(graph).unwrap());
+    }
+
+    #[test]
+    fn changes_to_nodes_round_trip() {
+        let (mut graph, ids) = house();
+        assert_round_trip(&mut graph, Operation::SetElement { node_id: ids[1].clone(), element: "Scullery".to_string() });
+        assert_round_trip(&mut graph, Operation::SetPosition { node_id: ids[1].clone(), position: Some(Position { x: 0, y: 1, z: 0 }) });
+        let mut metadata = graph.metadata().clone();
+        metadata.title = Some("The House".to_string());
+        assert_round_trip(&mut graph, Operation::SetMetadata { metadata });
+        let cellar = Node::new("Cellar".to_string());
+        assert_round_trip(&mut graph, Operation::AddNode { node: Rc::new(RefCell::new(cellar)) });
+    }
+
+    #[test]
+    fn changes_to_edges_round_trip_without_reordering_them() {
+        let (mut graph, ids) = house();
+        assert_round_trip(&mut graph, Operation::AddEdge { node_id: ids[0].clone(), edge: "west".to_string(), target_id: ids[3].clone() });
+        assert_round_trip(&mut graph, Operation::AddEdge { node_id: ids[0].clone(), edge: "north".to_string(), target_id: ids[3].clone() });
+        assert_round_trip(&mut graph, Operation::RetargetEdge { node_id: ids[0].clone(), edge: "north".to_string(), target_id: ids[2].clone() });
+        assert_round_trip(&mut graph, Operation::LinkBothWays { node_id: ids[2].clone(), edge: "east".to_string(), target_id: ids[3].clone() });
+        assert_round_trip(&mut graph, Operation::RemoveEdge { node_id: ids[0].clone(), edge: "north".to_string() });
+        assert_round_trip(&mut graph, Operation::Batch(vec![
+            Operation::RemoveEdge { node_id: ids[1].clone(), edge: "east".to_string() },
+            Operation::SetElement { node_id: ids[3].clone(), element: "Larder".to_string() },
+        ]));
+    }
+
+    #[test]
+    fn removing_the_current_node_round_trips_to_the_same_place() {
+        let (mut graph, ids) = house();
+        graph.current_node_id = ids[1].clone();
+        assert_round_trip(&mut graph, Operation::RemoveNode { node_id: ids[1].clone() });
+    }
+
+    #[test]
+    fn a_batch_that_fails_leaves_the_graph_and_history_alone() {
+        let (mut graph, ids) = house();
+        let mut history = History::new();
+        let before = snapshot(&graph);
+        let result = history.apply(&mut graph, Operation::Batch(vec![
+            Operation::RemoveEdge { node_id: ids[0].clone(), edge: "north".to_string() },
+            Operation::RemoveEdge { node_id: ids[0].clone(), edge: "nowhere".to_string() },
+        ]));
+        assert!(matches!(result, Err(GraphError::UnknownEdge(_))));
+        assert_eq!(before, snapshot(&graph));
+        assert!(!history.undo(&mut graph).unwrap());
+    }
+}
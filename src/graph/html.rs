@@ -0,0 +1,294 @@
+use std::fmt::{Display, Write};
+use std::hash::Hash;
+
+use serde::Serialize;
+use serde_derive::Serialize;
+
+use super::{Direction, Exporter, Graph};
+
+/// How the page looks.
+const STYLE: &str = r#"body { font-family: sans-serif; max-width: 48em; margin: 2em auto; padding: 0 1em; }
+#output { white-space: pre-wrap; font-family: monospace; }
+#exits button { margin: 0 0.5em 0.5em 0; }
+#command input { width: 20em; }"#;
+
+/// Plays the embedded map the same way as interactive mode, apart from items, conditions and saved
+/// games, which aren't part of a Graph.
+const ENGINE: &str = r#""use strict";
+const map = JSON.parse(document.getElementById("map").textContent);
+const output = document.getElementById("output");
+const exitButtons = document.getElementById("exits");
+const form = document.getElementById("command");
+const input = document.getElementById("input");
+const phrases = {
+    go: ["go", "walk", "move", "head"],
+    look: ["look", "l"],
+    help: ["help", "?"],
+    quit: ["quit", "exit", "q", "x"],
+};
+const usages = [
+    ["go <direction>, or just the direction", phrases.go],
+    ["look", phrases.look],
+    ["help", phrases.help],
+    ["quit", phrases.quit],
+];
+const visited = new Set();
+let current = map.start;
+
+function write(text) {
+    output.textContent += text + "\n";
+    window.scrollTo(0, document.body.scrollHeight);
+}
+
+function exits() {
+    return map.locations[current].exits;
+}
+
+// The exit that's called what the player entered, ignoring case, or that it's short for.
+function exactExit(entered) {
+    const wanted = entered.toLowerCase();
+    return exits().find(exit => exit.direction.toLowerCase() === wanted || (exit.aliases || []).includes(wanted));
+}
+
+function look() {
+    const location = map.locations[current];
+    const firstVisit = !visited.has(current);
+    visited.add(current);
+    write("");
+    write("Current Location: " + location.name);
+    write("    Description: " + (firstVisit && "firstVisit" in location ? location.firstVisit : location.description));
+    write("    Possible Directions: " + location.exits.map(exit => exit.direction).join(", "));
+    exitButtons.replaceChildren(...location.exits.map(exit => {
+        const button = document.createElement("button");
+        button.type = "button";
+        button.textContent = exit.direction;
+        button.addEventListener("click", () => run(exit.direction));
+        return button;
+    }));
+}
+
+// Move along the exit that the player means, as long as there's only one that it could be.
+function go(entered) {
+    const wanted = entered.toLowerCase();
+    const exact = exactExit(wanted);
+    const candidates = exact ? [exact] : exits().filter(exit => exit.direction.toLowerCase().startsWith(wanted));
+    if (candidates.length === 1) {
+        current = candidates[0].to;
+        look();
+    } else if (candidates.length > 1) {
+        write(`"${entered}" could be more than one direction. Did you mean: ${candidates.map(exit => exit.direction).join(", ")}?`);
+    } else {
+        write(`You can't go "${entered}" from here.`);
+    }
+}
+
+function help() {
+    write("You can:".padEnd(40) + "Words that work:");
+    for (const [usage, words] of usages) {
+        write("    " + usage.padEnd(36) + words.join(", "));
+    }
+}
+
+function quit() {
+    write("Thanks for playing.");
+    form.hidden = true;
+    exitButtons.replaceChildren();
+}
+
+function run(command) {
+    write("> " + command);
+    const entered = command.trim().replace(/\s+/g, " ");
+    const lower = entered.toLowerCase();
+    const verb = lower.split(" ")[0];
+    if (entered === "") {
+        write('Enter a command, or "help" to see what you can do.');
+    } else if (exactExit(lower)) {
+        go(entered);
+    } else if (phrases.look.includes(lower)) {
+        look();
+    } else if (phrases.help.includes(lower)) {
+        help();
+    } else if (phrases.quit.includes(lower)) {
+        quit();
+    } else if (phrases.go.includes(verb)) {
+        entered.length > verb.length ? go(entered.slice(verb.length + 1)) : write("Which way do you want to go?");
+    } else if (exits().some(exit => exit.direction.toLowerCase().startsWith(lower))) {
+        go(entered);
+    } else {
+        write(`I don't understand "${entered}".`);
+    }
+}
+
+form.addEventListener("submit", event => {
+    event.preventDefault();
+    run(input.value);
+    input.value = "";
+});
+write('Enter a direction to move, or "help" to see everything you can do.');
+look();"#;
+
+/// A node element that can be shown to the player when playing an exported map.
+pub trait Playable {
+    /// What the player is told when they're at the Node, depending on whether they've just arrived
+    /// for the first time.
+    fn description(&self, first_visit: bool) -> &str;
+}
+
+/// The map as it's embedded in the page, with Nodes numbered in the order they were added.
+#[derive(Serialize)]
+struct PlayableMap {
+    start: usize,
+    locations: Vec<PlayableLocation>,
+}
+
+#[derive(Serialize)]
+struct PlayableLocation {
+    name: String,
+    description: String,
+    /// Only included when it's different from the usual description.
+    #[serde(rename = "firstVisit", skip_serializing_if = "Option::is_none")]
+    first_visit: Option<String>,
+    exits: Vec<PlayableExit>,
+}
+
+#[derive(Serialize)]
+struct PlayableExit {
+    direction: String,
+    /// The number of the location that the exit leads to.
+    to: usize,
+    /// Other ways of entering a compass direction, in lower case.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    aliases: Vec<String>,
+}
+
+/// Escape text for use in HTML content or attribute values.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Exports a single HTML page that plays the map in a browser, starting from the root Node. The map
+/// is embedded as JSON and played by a small script, so the page works without anything else
+/// installed. Nodes are numbered rather than using their ids, so the same map always gives the same
+/// page.
+pub struct Html;
+
+impl<NodeElement: Serialize + Display + Playable, EdgeElement: Eq + Hash + Clone + Display> Exporter<NodeElement, EdgeElement> for Html {
+    fn export(&self, graph: &Graph<NodeElement, EdgeElement>) -> String {
+        let numbers = graph.node_numbers();
+        let locations = graph.nodes.values()
+            .map(|node| {
+                let node = node.borrow();
+                let description = node.element.description(false).to_string();
+                let first_visit = node.element.description(true);
+                PlayableLocation {
+                    name: node.element.to_string(),
+                    first_visit: (first_visit != description).then(|| first_visit.to_string()),
+                    description,
+                    // An exit to a Node that isn't in the Graph doesn't have anywhere to lead to.
+                    exits: node.edges()
+                        .filter_map(|(edge, target_id)| Some((edge, *numbers.get(target_id.as_str())?)))
+                        .map(|(edge, to)| {
+                            let direction = edge.to_string();
+                            let aliases = match direction.parse::<Direction>() {
+                                Ok(parsed) => [Some(parsed.name()), parsed.abbreviation()].into_iter().flatten().map(String::from).collect(),
+                                Err(_) => Vec::new(),
+                            };
+                            PlayableExit { direction, to, aliases }
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        let map = PlayableMap { start: numbers.get(graph.root_node_id.as_str()).copied().unwrap_or_default(), locations };
+        // Nothing in the JSON can end the script early once "<" is escaped.
+        let map = serde_json::to_string(&map).unwrap_or_default().replace('<', "\\u003c");
+
+        let title = escape(graph.metadata.title.as_deref().unwrap_or("Map"));
+        let mut html = String::from("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+        writeln!(html, "<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>", title, STYLE).unwrap();
+        writeln!(html, "<h1>{}</h1>", title).unwrap();
+        html.push_str("<div id=\"output\"></div>\n<div id=\"exits\"></div>\n");
+        html.push_str("<form id=\"command\"><label>What do you want to do? <input id=\"input\" autocomplete=\"off\" autofocus></label></form>\n");
+        writeln!(html, "<script type=\"application/json\" id=\"map\">{}</script>", map).unwrap();
+        writeln!(html, "<script>\n{}\n</script>\n</body>\n</html>", ENGINE).unwrap();
+        html
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::fmt::Formatter;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::graph::Node;
+
+    #[derive(Serialize)]
+    struct Room(&'static str, &'static str, Option<&'static str>);
+
+    impl Display for Room {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl Playable for Room {
+        fn description(&self, first_visit: bool) -> &str {
+            match self.2 {
+                Some(first_visit_description) if first_visit => first_visit_description,
+                _ => self.1,
+            }
+        }
+    }
+
+    /// A hall with a kitchen to the north that leads back south, and a cupboard with a script in
+    /// its description.
+    fn house() -> Graph<Room, String> {
+        let mut graph = Graph::new(Room("Hall", "A dusty hall.", Some("You step into a dusty hall.")));
+        let hall_id = graph.root_node_id().to_string();
+        let kitchen = Node::new(Room("Kitchen", "A cramped kitchen.", None));
+        let kitchen_id = kitchen.id.clone();
+        let cupboard = Node::new(Room("Cupboard", "</script><b>Boo</b>", None));
+        let cupboard_id = cupboard.id.clone();
+        graph.insert_node(Rc::new(RefCell::new(kitchen))).unwrap();
+        graph.insert_node(Rc::new(RefCell::new(cupboard))).unwrap();
+        graph.insert_edge(&hall_id, "north".to_string(), kitchen_id.clone()).unwrap();
+        graph.insert_edge(&kitchen_id, "south".to_string(), hall_id).unwrap();
+        graph.insert_edge(&kitchen_id, "into the cupboard".to_string(), cupboard_id).unwrap();
+        graph
+    }
+
+    #[test]
+    fn the_map_is_embedded_with_numbered_locations() {
+        let mut graph = house();
+        graph.metadata_mut().title = Some("Tom & Jerry's <House>".to_string());
+        let html = Html.export(&graph);
+        assert!(html.contains("<title>Tom &amp; Jerry's &lt;House&gt;</title>"), "{}", html);
+        assert!(html.contains(concat!(
+            r#"<script type="application/json" id="map">{"start":0,"locations":["#,
+            r#"{"name":"Hall","description":"A dusty hall.","firstVisit":"You step into a dusty hall.","exits":[{"direction":"north","to":1,"aliases":["north","n"]}]},"#,
+            r#"{"name":"Kitchen","description":"A cramped kitchen.","exits":[{"direction":"south","to":0,"aliases":["south","s"]},{"direction":"into the cupboard","to":2}]},"#,
+            r#"{"name":"Cupboard","description":"\u003c/script>\u003cb>Boo\u003c/b>","exits":[]}"#,
+            "]}</script>\n",
+        )), "{}", html);
+    }
+
+    #[test]
+    fn exits_to_missing_locations_are_left_out() {
+        let graph = house();
+        graph.node(graph.root_node_id()).unwrap().borrow_mut().insert_edge("down".to_string(), "missing".to_string());
+        let html = Html.export(&graph);
+        assert!(html.contains(r#""exits":[{"direction":"north","to":1,"aliases":["north","n"]}]}"#), "{}", html);
+        assert!(!html.contains("down"), "{}", html);
+    }
+
+    #[test]
+    fn the_same_map_always_gives_the_same_page() {
+        // Each house has its own ids, but they aren't part of the page.
+        assert_eq!(Html.export(&house()), Html.export(&house()));
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::hash::Hash;
+use std::str::FromStr;
+
+use linked_hash_map::LinkedHashMap;
+use serde::Serialize;
+use serde_derive::{Deserialize, Serialize};
+
+use super::{Direction, Graph};
+
+/// Where a Node is, with x increasing to the east, y to the north and z upwards. Maps that are flat
+/// leave z at 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub z: i32,
+}
+
+fn is_zero(value: &i32) -> bool {
+    *value == 0
+}
+
+impl Position {
+    pub fn new(x: i32, y: i32, z: i32) -> Self {
+        Position { x, y, z }
+    }
+
+    /// The position that's `offset` away from this one, unless it's beyond the furthest position
+    /// that can be stored.
+    pub fn moved(&self, offset: Position) -> Option<Position> {
+        Some(Position::new(self.x.checked_add(offset.x)?, self.y.checked_add(offset.y)?, self.z.checked_add(offset.z)?))
+    }
+}
+
+/// Positions are written as "x, y, z", leaving out z when it's 0.
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.z {
+            0 => write!(f, "{}, {}", self.x, self.y),
+            z => write!(f, "{}, {}, {}", self.x, self.y, z),
+        }
+    }
+}
+
+/// The text isn't two or three whole numbers separated by commas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidPosition;
+
+impl FromStr for Position {
+    type Err = InvalidPosition;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let coordinates = text.split(',')
+            .map(|coordinate| coordinate.trim().parse::<i32>())
+            .collect::<Result<Vec<i32>, _>>()
+            .map_err(|_| InvalidPosition)?;
+        match coordinates[..] {
+            [x, y] => Ok(Position::new(x, y, 0)),
+            [x, y, z] => Ok(Position::new(x, y, z)),
+            _ => Err(InvalidPosition),
+        }
+    }
+}
+
+/// Something about the compass edges that doesn't fit together when laying out a Graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutIssue {
+    /// Following `edge` from the Node for `node_id` should lead to `expected`, but the Node for
+    /// `target_id` had already been put at `actual`, like when going north and then south doesn't
+    /// lead back to the start.
+    Inconsistent { node_id: String, edge: String, target_id: String, expected: Position, actual: Position },
+    /// More than one Node was put at the same position.
+    Overlap { position: Position, node_ids: Vec<String> },
+    /// The Node can't be reached from the root by following compass edges, so it wasn't given a
+    /// position.
+    Unplaced(String),
+    /// Following `edge` from the Node for `node_id` would lead beyond the furthest position that can
+    /// be stored.
+    OutOfRange { node_id: String, edge: String },
+}
+
+impl Display for LayoutIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutIssue::Inconsistent { node_id, edge, target_id, expected, actual } =>
+                write!(f, "Going {} from the node with the id {} should lead to ({}), but it leads to {} at ({}).", edge, node_id, expected, target_id, actual),
+            LayoutIssue::Overlap { position, node_ids } =>
+                write!(f, "The nodes with the ids {} are all at ({}).", node_ids.join(", "), position),
+            LayoutIssue::Unplaced(node_id) =>
+                write!(f, "The node with the id {} can't be reached from the root using compass directions.", node_id),
+            LayoutIssue::OutOfRange { node_id, edge } =>
+                write!(f, "Going {} from the node with the id {} leads too far away to be given a position.", edge, node_id),
+        }
+    }
+}
+
+/// The positions worked out for the Nodes in a Graph, along with anything that didn't fit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Layout {
+    /// The position for each Node that could be placed, in the order they were reached from the
+    /// root.
+    pub positions: LinkedHashMap<String, Position>,
+    pub issues: Vec<LayoutIssue>,
+}
+
+/// How far a step along the edge moves, if it's a compass direction.
+pub(super) fn compass_offset<EdgeElement: Display>(edge: &EdgeElement) -> Option<Position> {
+    edge.to_string().parse::<Direction>().ok()?.offset()
+}
+
+impl<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone + Display> Graph<NodeElement, EdgeElement> {
+    /// Work out a position for every Node from the compass edges between them, starting from the
+    /// root's own position, or the origin if it doesn't have one. The first way that a Node is
+    /// reached decides its position, and any edge that disagrees is reported as inconsistent.
+    pub fn auto_layout(&self) -> Layout {
+        let mut layout = Layout::default();
+        let root_position = self.nodes.get(&self.root_node_id)
+            .and_then(|root| root.borrow().position)
+            .unwrap_or_default();
+        layout.positions.insert(self.root_node_id.clone(), root_position);
+        let mut queue = VecDeque::from([self.root_node_id.clone()]);
+        while let Some(node_id) = queue.pop_front() {
+            let position = layout.positions[&node_id];
+            let node = match self.nodes.get(&node_id) {
+                Some(node) => node.borrow(),
+                None => continue,
+            };
+            for (edge, target_id) in node.edges() {
+                let offset = match compass_offset(edge) {
+                    Some(offset) => offset,
+                    None => continue,
+                };
+                let expected = match position.moved(offset) {
+                    Some(expected) => expected,
+                    None => {
+                        layout.issues.push(LayoutIssue::OutOfRange { node_id: node_id.clone(), edge: edge.to_string() });
+                        continue;
+                    }
+                };
+                match layout.positions.get(target_id) {
+                    Some(actual) if *actual != expected => layout.issues.push(LayoutIssue::Inconsistent {
+                        node_id: node_id.clone(),
+                        edge: edge.to_string(),
+                        target_id: target_id.clone(),
+                        expected,
+                        actual: *actual,
+                    }),
+                    Some(_) => (),
+                    None if self.nodes.contains_key(target_id) => {
+                        layout.positions.insert(target_id.clone(), expected);
+                        queue.push_back(target_id.clone());
+                    }
+                    None => (),
+                }
+            }
+        }
+
+        let mut overlaps: Vec<(Position, Vec<String>)> = Vec::new();
+        for (node_id, position) in &layout.positions {
+            match overlaps.iter_mut().find(|(overlap_position, _)| overlap_position == position) {
+                Some((_, node_ids)) => node_ids.push(node_id.clone()),
+                None => overlaps.push((*position, vec![node_id.clone()])),
+            }
+        }
+        for (position, node_ids) in overlaps {
+            if node_ids.len() > 1 {
+                layout.issues.push(LayoutIssue::Overlap { position, node_ids });
+            }
+        }
+        for node_id in self.nodes.keys() {
+            if !layout.positions.contains_key(node_id) {
+                layout.issues.push(LayoutIssue::Unplaced(node_id.clone()));
+            }
+        }
+        layout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::test_support::map;
+
+    #[test]
+    fn positions_are_read_and_written_as_numbers() {
+        assert_eq!("1, -2".parse(), Ok(Position::new(1, -2, 0)));
+        assert_eq!("3,4,5".parse(), Ok(Position::new(3, 4, 5)));
+        assert_eq!("3".parse::<Position>(), Err(InvalidPosition));
+        assert_eq!("north".parse::<Position>(), Err(InvalidPosition));
+        assert_eq!(Position::new(1, -2, 0).to_string(), "1, -2");
+        assert_eq!(Position::new(3, 4, 5).to_string(), "3, 4, 5");
+    }
+
+    #[test]
+    fn compass_edges_decide_the_positions() {
+        let (graph, node_ids) = map(&["Hall", "Kitchen", "Attic", "Mirror"], &[
+            (0, "north", 1), (1, "south", 0), (1, "up", 2), (0, "through the mirror", 3),
+        ]);
+        graph.node(&node_ids[0]).unwrap().borrow_mut().position = Some(Position::new(5, 5, 0));
+        let layout = graph.auto_layout();
+        assert_eq!(layout.positions.iter().map(|(node_id, position)| (node_id.clone(), *position)).collect::<Vec<(String, Position)>>(), vec![
+            (node_ids[0].clone(), Position::new(5, 5, 0)),
+            (node_ids[1].clone(), Position::new(5, 6, 0)),
+            (node_ids[2].clone(), Position::new(5, 6, 1)),
+        ]);
+        assert_eq!(layout.issues, vec![LayoutIssue::Unplaced(node_ids[3].clone())]);
+    }
+
+    #[test]
+    fn edges_that_disagree_are_reported() {
+        // Going north and then south leads to the garden rather than back to the hall.
+        let (graph, node_ids) = map(&["Hall", "Kitchen", "Garden"], &[
+            (0, "north", 1), (0, "east", 2), (1, "south", 2),
+        ]);
+        let layout = graph.auto_layout();
+        assert_eq!(layout.issues, vec![LayoutIssue::Inconsistent {
+            node_id: node_ids[1].clone(),
+            edge: "south".to_string(),
+            target_id: node_ids[2].clone(),
+            expected: Position::new(0, 0, 0),
+            actual: Position::new(1, 0, 0),
+        }]);
+    }
+
+    #[test]
+    fn positions_past_the_furthest_one_are_reported() {
+        let (graph, node_ids) = map(&["Hall", "Kitchen", "Garden"], &[(0, "east", 1), (0, "west", 2)]);
+        graph.node(&node_ids[0]).unwrap().borrow_mut().position = Some(Position::new(i32::MAX, 0, 0));
+        let layout = graph.auto_layout();
+        assert_eq!(layout.positions.get(&node_ids[2]), Some(&Position::new(i32::MAX - 1, 0, 0)));
+        assert_eq!(layout.issues, vec![
+            LayoutIssue::OutOfRange { node_id: node_ids[0].clone(), edge: "east".to_string() },
+            LayoutIssue::Unplaced(node_ids[1].clone()),
+        ]);
+    }
+
+    #[test]
+    fn nodes_in_the_same_place_are_reported() {
+        let (graph, node_ids) = map(&["Hall", "Kitchen", "Garden", "Shed"], &[
+            (0, "north", 1), (0, "east", 2), (2, "northwest", 3),
+        ]);
+        assert_eq!(graph.auto_layout().issues, vec![LayoutIssue::Overlap {
+            position: Position::new(0, 1, 0),
+            node_ids: vec![node_ids[1].clone(), node_ids[3].clone()],
+        }]);
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt::Display;
+
+/// What was found when looking for the edge that some input refers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EdgeMatch<EdgeElement> {
+    /// Exactly one edge matches.
+    Found(EdgeElement),
+    /// More than one edge matches equally well, so the input has to be narrowed down.
+    Ambiguous(Vec<EdgeElement>),
+    /// Nothing matches.
+    NotFound,
+}
+
+impl<EdgeElement> EdgeMatch<EdgeElement> {
+    /// A match for however many candidates there are.
+    pub fn from_candidates(mut candidates: Vec<EdgeElement>) -> Self {
+        match candidates.len() {
+            0 => EdgeMatch::NotFound,
+            1 => EdgeMatch::Found(candidates.remove(0)),
+            _ => EdgeMatch::Ambiguous(candidates),
+        }
+    }
+}
+
+/// A strategy for deciding which edge some input refers to.
+pub trait EdgeMatcher<EdgeElement> {
+    fn find(&self, input: &str, edges: &[EdgeElement]) -> EdgeMatch<EdgeElement>;
+}
+
+/// Matches an edge whose text is exactly the input.
+pub struct Exact;
+
+impl<EdgeElement: Display + Clone> EdgeMatcher<EdgeElement> for Exact {
+    fn find(&self, input: &str, edges: &[EdgeElement]) -> EdgeMatch<EdgeElement> {
+        EdgeMatch::from_candidates(edges.iter().filter(|edge| edge.to_string() == input).cloned().collect())
+    }
+}
+
+/// Matches an edge whose text is the input, ignoring case and surrounding whitespace.
+pub struct CaseInsensitive;
+
+impl<EdgeElement: Display + Clone> EdgeMatcher<EdgeElement> for CaseInsensitive {
+    fn find(&self, input: &str, edges: &[EdgeElement]) -> EdgeMatch<EdgeElement> {
+        let input = input.trim().to_lowercase();
+        EdgeMatch::from_candidates(edges.iter().filter(|edge| edge.to_string().to_lowercase() == input).cloned().collect())
+    }
+}
+
+/// Matches the edges whose text starts with the input, ignoring case, so "nor" finds "north" as long
+/// as nothing else starts the same way.
+pub struct UniquePrefix;
+
+impl<EdgeElement: Display + Clone> EdgeMatcher<EdgeElement> for UniquePrefix {
+    fn find(&self, input: &str, edges: &[EdgeElement]) -> EdgeMatch<EdgeElement> {
+        let input = input.trim().to_lowercase();
+        if input.is_empty() {
+            return EdgeMatch::NotFound;
+        }
+        EdgeMatch::from_candidates(edges.iter().filter(|edge| edge.to_string().to_lowercase().starts_with(&input)).cloned().collect())
+    }
+}
+
+/// Matches the edges that are the fewest edits away from the input, ignoring case, as long as they're
+/// no more than `max_distance` edits away. Edges aren't matched when every character would have to
+/// change, so a single letter doesn't match every other single letter.
+pub struct EditDistance {
+    pub max_distance: usize,
+}
+
+impl<EdgeElement: Display + Clone> EdgeMatcher<EdgeElement> for EditDistance {
+    fn find(&self, input: &str, edges: &[EdgeElement]) -> EdgeMatch<EdgeElement> {
+        let input = input.trim().to_lowercase();
+        let distances = edges.iter()
+            .map(|edge| {
+                let text = edge.to_string().to_lowercase();
+                (edit_distance(&input, &text), text.chars().count())
+            })
+            .collect::<Vec<(usize, usize)>>();
+        let closest = distances.iter()
+            .filter(|(distance, length)| *distance <= self.max_distance && distance < length)
+            .map(|(distance, _)| *distance)
+            .min();
+        match closest {
+            Some(closest) => EdgeMatch::from_candidates(edges.iter()
+                .zip(&distances)
+                .filter(|(_, (distance, _))| *distance == closest)
+                .map(|(edge, _)| edge.clone())
+                .collect()),
+            None => EdgeMatch::NotFound,
+        }
+    }
+}
+
+/// Tries each matcher in turn, settling on the first one that finds anything, whether or not it's
+/// ambiguous.
+pub struct Fallback<EdgeElement>(pub Vec<Box<dyn EdgeMatcher<EdgeElement>>>);
+
+impl<EdgeElement> EdgeMatcher<EdgeElement> for Fallback<EdgeElement> {
+    fn find(&self, input: &str, edges: &[EdgeElement]) -> EdgeMatch<EdgeElement> {
+        for matcher in &self.0 {
+            match matcher.find(input, edges) {
+                EdgeMatch::NotFound => (),
+                found => return found,
+            }
+        }
+        EdgeMatch::NotFound
+    }
+}
+
+/// The Levenshtein distance between two strings: how many single character insertions, deletions
+/// or substitutions it takes to turn one into the other.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut previous_row = (0..=b.len()).collect::<Vec<usize>>();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution_cost = if a_char == *b_char { 0 } else { 1 };
+            row[j + 1] = (previous_row[j] + substitution_cost).min(previous_row[j + 1] + 1).min(row[j] + 1);
+        }
+        previous_row = row;
+    }
+    previous_row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edges() -> Vec<String> {
+        ["north", "northeast", "East", "up the stairs"].iter().map(|edge| edge.to_string()).collect()
+    }
+
+    fn found(edge: &str) -> EdgeMatch<String> {
+        EdgeMatch::Found(edge.to_string())
+    }
+
+    #[test]
+    fn exact_and_case_insensitive_matching() {
+        assert_eq!(Exact.find("East", &edges()), found("East"));
+        assert_eq!(Exact.find("east", &edges()), EdgeMatch::NotFound);
+        assert_eq!(CaseInsensitive.find(" EAST ", &edges()), found("East"));
+    }
+
+    #[test]
+    fn prefixes_have_to_be_unique() {
+        assert_eq!(UniquePrefix.find("up", &edges()), found("up the stairs"));
+        assert_eq!(UniquePrefix.find("nor", &edges()), EdgeMatch::Ambiguous(vec!["north".to_string(), "northeast".to_string()]));
+        assert_eq!(UniquePrefix.find("", &edges()), EdgeMatch::NotFound);
+    }
+
+    #[test]
+    fn the_closest_edges_within_the_distance_match() {
+        let matcher = EditDistance { max_distance: 2 };
+        assert_eq!(matcher.find("nrth", &edges()), found("north"));
+        assert_eq!(matcher.find("est", &edges()), found("East"));
+        assert_eq!(matcher.find("down", &edges()), EdgeMatch::NotFound);
+        assert_eq!(EditDistance { max_distance: 1 }.find("e", &["w".to_string()]), EdgeMatch::NotFound);
+    }
+
+    #[test]
+    fn fallback_uses_the_first_matcher_that_finds_anything() {
+        let matcher: Fallback<String> = Fallback(vec![Box::new(CaseInsensitive), Box::new(UniquePrefix)]);
+        assert_eq!(matcher.find("north", &edges()), found("north"));
+        assert_eq!(matcher.find("northe", &edges()), found("northeast"));
+        assert_eq!(matcher.find("west", &edges()), EdgeMatch::NotFound);
+    }
+
+    #[test]
+    fn edit_distance_counts_single_character_changes() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+}
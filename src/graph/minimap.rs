@@ -0,0 +1,225 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+use std::hash::Hash;
+
+use serde::Serialize;
+
+use super::{Graph, GraphError, Position};
+use super::layout::compass_offset;
+
+/// Marks the Node that the mini-map is drawn around.
+const CENTRE_KEY: char = '@';
+/// Mark the other Nodes, in the order that they're reached.
+const KEYS: &str = "123456789abcdefghijklmnopqrstuvwxyz";
+/// Marks every Node that's reached after the keys have run out.
+const SPARE_KEY: char = '+';
+/// How many characters across each Node takes up, including the gap before the next one.
+const CELL_WIDTH: i32 = 4;
+/// How many lines each Node takes up, including the gap before the next one.
+const CELL_HEIGHT: i32 = 2;
+
+/// How far across and up a flat grid something is.
+type GridPosition = (i32, i32);
+
+impl<NodeElement: Serialize + Display, EdgeElement: Eq + Hash + Clone + Display> Graph<NodeElement, EdgeElement> {
+    /// Draw the Nodes within `distance` compass steps of the Node for `node_id` on a grid, joined by
+    /// the compass edges between them and followed by a key that names them.
+    ///
+    /// When the Node for `node_id` has a position, other Nodes with positions on the same level are
+    /// drawn where they are, and the rest are drawn where the compass edges lead. Up, down, in, out
+    /// and custom edges can't be drawn, so they're listed in the key instead. When two Nodes would be
+    /// drawn in the same place, only the first one that's reached is drawn and the other is listed as
+    /// not fitting.
+    pub fn minimap(&self, node_id: &str, distance: usize) -> Result<String, GraphError> {
+        // No Node can be more steps away than there are Nodes, which also keeps the grid in bounds
+        // however far the map is asked to go.
+        let distance = distance.min(self.nodes.len());
+        let centre_position = self.node(node_id)?.borrow().position;
+        let mut placed: Vec<(String, GridPosition)> = vec![(node_id.to_string(), (0, 0))];
+        let mut placed_at: HashMap<GridPosition, String> = HashMap::from([((0, 0), node_id.to_string())]);
+        let mut crowded_out: Vec<String> = Vec::new();
+        let mut queue = VecDeque::from([(node_id.to_string(), (0, 0), 0)]);
+        while let Some((node_id, (x, y), steps)) = queue.pop_front() {
+            if steps == distance {
+                continue;
+            }
+            for ((dx, dy), target_id) in self.grid_edges(&node_id)? {
+                if placed.iter().any(|(placed_id, _)| *placed_id == target_id) {
+                    continue;
+                }
+                let position = match (centre_position, self.node(&target_id)?.borrow().position) {
+                    (Some(centre), Some(target)) if target.z == centre.z => grid_position(centre, target, distance),
+                    _ => None,
+                };
+                let position = position.unwrap_or((x + dx, y + dy));
+                if placed_at.contains_key(&position) {
+                    if !crowded_out.contains(&target_id) {
+                        crowded_out.push(target_id);
+                    }
+                    continue;
+                }
+                placed_at.insert(position, target_id.clone());
+                placed.push((target_id.clone(), position));
+                queue.push_back((target_id, position, steps + 1));
+            }
+        }
+        // A Node that didn't fit one way might still have been reached another way.
+        crowded_out.retain(|crowded_id| !placed.iter().any(|(placed_id, _)| placed_id == crowded_id));
+
+        let min_x = placed.iter().map(|(_, (x, _))| *x).min().unwrap_or_default();
+        let max_x = placed.iter().map(|(_, (x, _))| *x).max().unwrap_or_default();
+        let min_y = placed.iter().map(|(_, (_, y))| *y).min().unwrap_or_default();
+        let max_y = placed.iter().map(|(_, (_, y))| *y).max().unwrap_or_default();
+        let mut grid = vec![vec![' '; ((max_x - min_x) * CELL_WIDTH + 3) as usize]; ((max_y - min_y) * CELL_HEIGHT + 1) as usize];
+        let mut set = |row: i32, column: i32, character: char| {
+            let cell = &mut grid[row as usize][column as usize];
+            *cell = match (*cell, character) {
+                ('/', '\\') | ('\\', '/') => 'X',
+                _ => character,
+            };
+        };
+        let mut key_lines = Vec::new();
+        for (idx, (node_id, (x, y))) in placed.iter().enumerate() {
+            let key = match idx {
+                0 => CENTRE_KEY,
+                _ => KEYS.chars().nth(idx - 1).unwrap_or(SPARE_KEY),
+            };
+            let (row, column) = ((max_y - y) * CELL_HEIGHT, (x - min_x) * CELL_WIDTH);
+            set(row, column, '[');
+            set(row, column + 1, key);
+            set(row, column + 2, ']');
+            // Edges are only drawn when they lead to where their Node actually ended up.
+            for ((dx, dy), target_id) in self.grid_edges(node_id)? {
+                if placed_at.get(&(x + dx, y + dy)) != Some(&target_id) {
+                    continue;
+                }
+                let connector = match (dx, dy) {
+                    (_, 0) => '-',
+                    (0, _) => '|',
+                    _ if dx == dy => '/',
+                    _ => '\\',
+                };
+                set(row - dy, column + 1 + dx * 2, connector);
+            }
+
+            let node = self.node(node_id)?;
+            let node = node.borrow();
+            let other_edges = node.edges()
+                .map(|(edge, _)| edge.to_string())
+                .filter(|edge| !matches!(compass_offset(edge), Some(offset) if offset.z == 0))
+                .collect::<Vec<String>>();
+            match other_edges.is_empty() {
+                true => key_lines.push(format!("{} {}", key, node.element)),
+                false => key_lines.push(format!("{} {} (also {})", key, node.element, other_edges.join(", "))),
+            }
+        }
+
+        let mut lines = grid.into_iter()
+            .map(|row| row.into_iter().collect::<String>().trim_end().to_string())
+            .collect::<Vec<String>>();
+        lines.push(String::new());
+        lines.append(&mut key_lines);
+        if !crowded_out.is_empty() {
+            let names = crowded_out.iter()
+                .map(|crowded_id| self.node(crowded_id).map(|node| node.borrow().element.to_string()))
+                .collect::<Result<Vec<String>, GraphError>>()?;
+            lines.push(format!("Not shown because something else is in the way: {}", names.join(", ")));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// The edges from the Node for the id that lead across the grid, as how far they move across and
+    /// up it along with the id of the Node they lead to.
+    fn grid_edges(&self, node_id: &str) -> Result<Vec<(GridPosition, String)>, GraphError> {
+        Ok(self.node(node_id)?.borrow().edges()
+            .filter_map(|(edge, target_id)| match compass_offset(edge) {
+                Some(offset) if offset.z == 0 => Some(((offset.x, offset.y), target_id.clone())),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+/// Where the target is on the grid when the centre is in the middle of it. Positions that are more
+/// than `distance` away across or up aren't used, since they'd make the grid much bigger than the
+/// steps to them need.
+fn grid_position(centre: Position, target: Position, distance: usize) -> Option<GridPosition> {
+    let (x, y) = (target.x.checked_sub(centre.x)?, target.y.checked_sub(centre.y)?);
+    (x.unsigned_abs() as usize <= distance && y.unsigned_abs() as usize <= distance).then_some((x, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::test_support::map;
+
+    #[test]
+    fn nearby_locations_are_drawn_around_the_centre() {
+        let (graph, node_ids) = map(&["Hall", "Kitchen", "Garden", "Attic", "Shed"], &[
+            (0, "north", 1), (1, "south", 0),
+            (0, "e", 2), (2, "northwest", 1),
+            (0, "up", 3),
+            (2, "east", 4),
+        ]);
+        assert_eq!(graph.minimap(&node_ids[0], 1).unwrap(), "\
+[1]
+ | \\
+[@]-[2]
+
+@ Hall (also up)
+1 Kitchen
+2 Garden");
+        assert_eq!(graph.minimap(&node_ids[0], 2).unwrap().lines().nth(2), Some("[@]-[2]-[3]"));
+        assert_eq!(graph.minimap(&node_ids[0], 0).unwrap(), "[@]\n\n@ Hall (also up)");
+    }
+
+    #[test]
+    fn locations_that_would_overlap_are_listed_instead() {
+        // Going east and then northwest ends up in the same place as going north.
+        let (graph, node_ids) = map(&["Hall", "Kitchen", "Garden", "Greenhouse"], &[
+            (0, "north", 1),
+            (0, "east", 2),
+            (2, "northwest", 3),
+            (1, "sw", 0), (3, "southeast", 2),
+        ]);
+        assert_eq!(graph.minimap(&node_ids[0], 2).unwrap(), "\
+[1]
+ |
+[@]-[2]
+
+@ Hall
+1 Kitchen
+2 Garden
+Not shown because something else is in the way: Greenhouse");
+    }
+
+    #[test]
+    fn crossing_diagonals_are_drawn_as_an_x() {
+        let (graph, node_ids) = map(&["Hall", "Kitchen", "Garden", "Shed"], &[
+            (0, "east", 1), (0, "northeast", 2), (1, "northwest", 3),
+        ]);
+        assert_eq!(graph.minimap(&node_ids[0], 2).unwrap().lines().take(3).collect::<Vec<&str>>(), vec!["[3] [2]", "   X", "[@]-[1]"]);
+    }
+
+    #[test]
+    fn positions_are_used_when_there_are_some() {
+        let (graph, node_ids) = map(&["Hall", "Kitchen", "Garden"], &[(0, "north", 1), (0, "east", 2)]);
+        for (node_id, position) in node_ids.iter().zip([Position::new(0, 0, 0), Position::new(0, 2, 0), Position::new(1, 0, 1)]) {
+            graph.node(node_id).unwrap().borrow_mut().position = Some(position);
+        }
+        // The kitchen is further away than the edge suggests, so they aren't joined up, and the
+        // garden is on another level, so it's drawn where the edge leads.
+        assert_eq!(graph.minimap(&node_ids[0], 2).unwrap().lines().take(5).collect::<Vec<&str>>(), vec!["[1]", "", "", "", "[@]-[2]"]);
+        // Positions further away than the map goes aren't used.
+        assert_eq!(graph.minimap(&node_ids[0], 1).unwrap().lines().take(3).collect::<Vec<&str>>(), vec!["[1]", " |", "[@]-[2]"]);
+    }
+
+    #[test]
+    fn positions_at_the_far_ends_are_not_used() {
+        let (graph, node_ids) = map(&["Hall", "Kitchen", "Garden"], &[(0, "east", 1), (0, "west", 2)]);
+        for (node_id, x) in node_ids.iter().zip([i32::MAX, i32::MIN, i32::MAX - 1]) {
+            graph.node(node_id).unwrap().borrow_mut().position = Some(Position::new(x, 0, 0));
+        }
+        assert_eq!(graph.minimap(&node_ids[0], usize::MAX).unwrap().lines().next(), Some("[2]-[@]-[1]"));
+    }
+}
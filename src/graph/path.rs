@@ -0,0 +1,99 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+use serde::Serialize;
+
+use super::Graph;
+
+impl<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone> Graph<NodeElement, EdgeElement> {
+    /// Find the fewest edges that need to be followed to get from the Node for `from` to the Node
+    /// for `to`, using a breadth-first search. Returns an empty route if both ids are the same and
+    /// `None` if there isn't a way to get there. When more than one route is as short as the others,
+    /// the one whose edges were added first wins.
+    pub fn shortest_path(&self, from: &str, to: &str) -> Option<Vec<EdgeElement>> {
+        if !self.nodes.contains_key(from) || !self.nodes.contains_key(to) {
+            return None;
+        }
+        // Remember how each Node was first reached so the route can be rebuilt backwards from the
+        // destination once it's found.
+        let mut reached_by: HashMap<String, (String, EdgeElement)> = HashMap::new();
+        let mut queue = VecDeque::from([from.to_string()]);
+        while let Some(node_id) = queue.pop_front() {
+            if node_id == to {
+                let mut route = Vec::new();
+                let mut step_id = node_id;
+                while let Some((previous_id, edge)) = reached_by.remove(&step_id) {
+                    route.push(edge);
+                    step_id = previous_id;
+                }
+                route.reverse();
+                return Some(route);
+            }
+            for (edge, target_id) in self.nodes[&node_id].borrow().edges.iter() {
+                if target_id != from && self.nodes.contains_key(target_id) && !reached_by.contains_key(target_id) {
+                    reached_by.insert(target_id.clone(), (node_id.clone(), edge.clone()));
+                    queue.push_back(target_id.clone());
+                }
+            }
+        }
+        None
+    }
+
+    /// The fewest edges that need to be followed to get from the Node for `node_id` to every Node
+    /// that can be reached from it, keyed by id. The Node itself is at a distance of 0.
+    pub fn distances_from(&self, node_id: &str) -> HashMap<String, usize> {
+        let mut distances = HashMap::new();
+        if !self.nodes.contains_key(node_id) {
+            return distances;
+        }
+        distances.insert(node_id.to_string(), 0);
+        let mut queue = VecDeque::from([node_id.to_string()]);
+        while let Some(next_id) = queue.pop_front() {
+            let distance = distances[&next_id] + 1;
+            for (_, target_id) in self.nodes[&next_id].borrow().edges.iter() {
+                if self.nodes.contains_key(target_id) && !distances.contains_key(target_id) {
+                    distances.insert(target_id.clone(), distance);
+                    queue.push_back(target_id.clone());
+                }
+            }
+        }
+        distances
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::test_support::map;
+
+    #[test]
+    fn the_route_with_the_fewest_edges_is_found() {
+        // The hall leads to the study both directly and through the kitchen.
+        let (graph, node_ids) = map(&["Hall", "Kitchen", "Study", "Attic"], &[
+            (0, "north", 1), (1, "east", 2), (0, "through the hatch", 2), (2, "up", 3),
+        ]);
+        assert_eq!(graph.shortest_path(&node_ids[0], &node_ids[3]), Some(vec!["through the hatch".to_string(), "up".to_string()]));
+        assert_eq!(graph.shortest_path(&node_ids[1], &node_ids[1]), Some(Vec::new()));
+        assert_eq!(graph.shortest_path(&node_ids[3], &node_ids[0]), None);
+        assert_eq!(graph.shortest_path(&node_ids[0], "missing"), None);
+    }
+
+    #[test]
+    fn ties_go_to_the_edges_that_were_added_first() {
+        let (graph, node_ids) = map(&["Hall", "Kitchen", "Pantry", "Garden"], &[
+            (0, "north", 1), (0, "east", 2), (1, "out", 3), (2, "out", 3),
+        ]);
+        assert_eq!(graph.shortest_path(&node_ids[0], &node_ids[3]), Some(vec!["north".to_string(), "out".to_string()]));
+    }
+
+    #[test]
+    fn distances_only_include_the_nodes_that_can_be_reached() {
+        let (graph, node_ids) = map(&["Hall", "Kitchen", "Garden", "Attic"], &[
+            (0, "north", 1), (1, "south", 0), (1, "out", 2),
+        ]);
+        let distances = graph.distances_from(&node_ids[0]);
+        assert_eq!(distances.len(), 3);
+        assert_eq!((distances[&node_ids[0]], distances[&node_ids[1]], distances[&node_ids[2]]), (0, 1, 2));
+        assert_eq!(graph.distances_from(&node_ids[3]).len(), 1);
+        assert!(graph.distances_from("missing").is_empty());
+    }
+}
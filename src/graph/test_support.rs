@@ -0,0 +1,21 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::{Graph, Node};
+
+/// A Graph of the named Nodes, with the first one as the root, joined by the edges between the
+/// Nodes at the provided positions in the list of names. The ids of the Nodes are returned in the
+/// same order as the names.
+pub fn map(names: &[&str], edges: &[(usize, &str, usize)]) -> (Graph<String, String>, Vec<String>) {
+    let mut graph = Graph::new(names[0].to_string());
+    let mut node_ids = vec![graph.root_node_id().to_string()];
+    for name in &names[1..] {
+        let node = Node::new(name.to_string());
+        node_ids.push(node.id.clone());
+        graph.insert_node(Rc::new(RefCell::new(node))).unwrap();
+    }
+    for (from, edge, to) in edges {
+        graph.insert_edge(&node_ids[*from], edge.to_string(), node_ids[*to].clone()).unwrap();
+    }
+    (graph, node_ids)
+}
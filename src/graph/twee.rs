@@ -0,0 +1,564 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Write};
+use std::hash::Hash;
+use std::rc::Rc;
+
+use linked_hash_map::LinkedHashMap;
+use serde::Serialize;
+use serde_derive::{Deserialize, Serialize};
+
+use super::{Exporter, Graph, Metadata, Node};
+
+/// Starts the header of every passage.
+const PASSAGE_MARKER: &str = "::";
+/// Escapes the characters that have a meaning in passage headers, and passage text that would
+/// otherwise be read as a header.
+const ESCAPE: char = '\\';
+/// The passage that holds the title of the story.
+const STORY_TITLE: &str = "StoryTitle";
+/// The passage that holds the details of the story as JSON.
+const STORY_DATA: &str = "StoryData";
+/// The passage that Twine starts from when the story data doesn't name one.
+const DEFAULT_START: &str = "Start";
+
+/// A node element that can be written as a Twine passage.
+pub trait Passage {
+    /// The name that links use to lead to the passage.
+    fn passage_name(&self) -> &str;
+    fn passage_tags(&self) -> Vec<String>;
+    /// The text of the passage, without any links.
+    fn passage_text(&self) -> &str;
+    fn from_passage(name: String, tags: Vec<String>, text: String) -> Self;
+}
+
+/// Reasons that a Twine story can't be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TweeError {
+    /// There aren't any passages to turn into Nodes.
+    NoPassages,
+    /// More than one passage has the provided name, so links to it are ambiguous.
+    DuplicatePassage(String),
+    /// The story data says to start from a passage that doesn't exist.
+    UnknownStart(String),
+    /// The story data isn't valid JSON.
+    InvalidStoryData(String),
+}
+
+impl Display for TweeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TweeError::NoPassages => write!(f, "The story doesn't have any passages."),
+            TweeError::DuplicatePassage(name) => write!(f, "More than one passage is called {}.", name),
+            TweeError::UnknownStart(name) => write!(f, "The story starts from {}, which isn't a passage.", name),
+            TweeError::InvalidStoryData(e) => write!(f, "The story data can't be read: {}", e),
+        }
+    }
+}
+
+impl Error for TweeError {}
+
+/// Things that couldn't be kept as they were when reading or writing a Twine story.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TweeIssue {
+    /// A link in the `passage` leads to a `target` passage that doesn't exist.
+    BrokenLink { passage: String, target: String },
+    /// The `passage` has more than one link with the same `text`, so only the first one was kept.
+    DuplicateLink { passage: String, text: String },
+    /// A `tag` on the `passage` has spaces in it, so it's written with dashes and won't have its
+    /// spaces back when the story is read again.
+    SpacedTag { passage: String, tag: String },
+    /// The `passage` has a name that would break the links to it, so it's written as `renamed`.
+    RenamedPassage { passage: String, renamed: String },
+    /// A link from the `passage` has `text` that would break the link, so it's written as `renamed`.
+    RenamedLink { passage: String, text: String, renamed: String },
+}
+
+impl Display for TweeIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TweeIssue::BrokenLink { passage, target } =>
+                write!(f, "The link from {} to {} was left out because there isn't a passage called {}.", passage, target, target),
+            TweeIssue::DuplicateLink { passage, text } =>
+                write!(f, "{} has more than one link called {}, so only the first one was kept.", passage, text),
+            TweeIssue::SpacedTag { passage, tag } =>
+                write!(f, "Twine doesn't allow spaces in tags, so the {} tag on {} is written as {}.", tag, passage, dashed(tag)),
+            TweeIssue::RenamedPassage { passage, renamed } =>
+                write!(f, "Twine links can't lead to {}, so it's written as {}.", passage, renamed),
+            TweeIssue::RenamedLink { passage, text, renamed } =>
+                write!(f, "Twine links can't be called {}, so the one from {} is written as {}.", text, passage, renamed),
+        }
+    }
+}
+
+/// The parts of the StoryData passage that are kept. Twine writes more, which is ignored.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct StoryData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ifid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    start: Option<String>,
+}
+
+/// A passage as it was written, before its links have been read.
+struct RawPassage {
+    name: String,
+    tags: Vec<String>,
+    text: String,
+}
+
+/// Read text up to the first of the `stops` that isn't escaped, returning the text without its
+/// escapes and whatever is left, starting from the stop.
+fn read_escaped<'a>(text: &'a str, stops: &[char]) -> (String, &'a str) {
+    let mut read = String::new();
+    let mut characters = text.char_indices();
+    while let Some((idx, character)) = characters.next() {
+        if character == ESCAPE {
+            if let Some((_, escaped)) = characters.next() {
+                read.push(escaped);
+            }
+        } else if stops.contains(&character) {
+            return (read, &text[idx..]);
+        } else {
+            read.push(character);
+        }
+    }
+    (read, "")
+}
+
+/// Escape the characters that have a meaning in passage headers.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        if matches!(character, '\\' | '[' | ']' | '{' | '}') {
+            escaped.push(ESCAPE);
+        }
+        escaped.push(character);
+    }
+    escaped
+}
+
+/// Text that can be used as the link text or target of a link. Twine can't escape anything inside a
+/// link, so the brackets that would end it are turned into parentheses, "|" into "/" and the arrows
+/// that would split it into dashes.
+fn linkable(text: &str) -> String {
+    text.replace("->", "-")
+        .replace("<-", "-")
+        .replace('|', "/")
+        .replace('[', "(")
+        .replace(']', ")")
+}
+
+/// A tag as it's written in a passage header, with its spaces turned into dashes.
+fn dashed(tag: &str) -> String {
+    tag.split_whitespace().collect::<Vec<&str>>().join("-")
+}
+
+/// Split Twee source into its passages. Anything before the first passage header is ignored, as are
+/// the metadata on each header and the blank lines between passages.
+fn read_passages(twee: &str) -> Vec<RawPassage> {
+    let mut passages: Vec<RawPassage> = Vec::new();
+    for line in twee.lines() {
+        if let Some(header) = line.strip_prefix(PASSAGE_MARKER) {
+            let (name, rest) = read_escaped(header, &['[', '{']);
+            let tags = match rest.strip_prefix('[') {
+                Some(rest) => read_escaped(rest, &[']']).0.split_whitespace().map(String::from).collect(),
+                None => Vec::new(),
+            };
+            passages.push(RawPassage { name: name.trim().to_string(), tags, text: String::new() });
+        } else if let Some(passage) = passages.last_mut() {
+            // Text that would be read as a header has an escape in front of it.
+            let line = match line.strip_prefix(ESCAPE) {
+                Some(escaped) if escaped.starts_with(PASSAGE_MARKER) => escaped,
+                _ => line,
+            };
+            passage.text.push_str(line);
+            passage.text.push('\n');
+        }
+    }
+    for passage in &mut passages {
+        passage.text = passage.text.trim_end().to_string();
+    }
+    passages
+}
+
+/// Split a link into its link text and the name of the passage it leads to. Twine reads the link
+/// from the last "->", the first "<-" or the first "|", in that order, and ignores any setter
+/// after "][".
+fn read_link(link: &str) -> (String, String) {
+    let link = link.split("][").next().unwrap_or(link);
+    let (text, target) = if let Some((text, target)) = link.rsplit_once("->") {
+        (text, target)
+    } else if let Some((target, text)) = link.split_once("<-") {
+        (text, target)
+    } else if let Some((text, target)) = link.split_once('|') {
+        (text, target)
+    } else {
+        (link, link)
+    };
+    (text.trim().to_string(), target.trim().to_string())
+}
+
+/// Take the links out of passage text, returning the text that's left and every link as its link
+/// text and the name of the passage it leads to. Lines that only have links on them are taken out
+/// altogether, while links in the middle of other text are replaced by their link text.
+fn read_links(text: &str) -> (String, Vec<(String, String)>) {
+    let mut links = Vec::new();
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let (mut kept, mut other_text) = (String::new(), String::new());
+        let mut rest = line;
+        while let Some(start) = rest.find("[[") {
+            let length = match rest[start + 2..].find("]]") {
+                Some(length) => length,
+                None => break,
+            };
+            let (link_text, target) = read_link(&rest[start + 2..start + 2 + length]);
+            kept.push_str(&rest[..start]);
+            other_text.push_str(&rest[..start]);
+            kept.push_str(&link_text);
+            links.push((link_text, target));
+            rest = &rest[start + 2 + length + 2..];
+        }
+        kept.push_str(rest);
+        other_text.push_str(rest);
+        if kept == other_text || !other_text.trim().is_empty() {
+            lines.push(kept);
+        }
+    }
+    (lines.join("\n").trim().to_string(), links)
+}
+
+impl<NodeElement: Serialize + Passage, EdgeElement: Eq + Hash + Clone + Display> Graph<NodeElement, EdgeElement> {
+    /// The names, links and tags that won't be read back the same after the Graph has been exported
+    /// as Twee.
+    pub fn twee_issues(&self) -> Vec<TweeIssue> {
+        let mut issues = Vec::new();
+        for node in self.nodes.values() {
+            let node = node.borrow();
+            let passage = node.element.passage_name();
+            if linkable(passage) != passage {
+                issues.push(TweeIssue::RenamedPassage { passage: passage.to_string(), renamed: linkable(passage) });
+            }
+            for tag in node.element.passage_tags() {
+                if dashed(&tag) != tag {
+                    issues.push(TweeIssue::SpacedTag { passage: passage.to_string(), tag });
+                }
+            }
+            for edge in node.edges.keys() {
+                let text = edge.to_string();
+                if linkable(&text) != text {
+                    issues.push(TweeIssue::RenamedLink { passage: passage.to_string(), renamed: linkable(&text), text });
+                }
+            }
+        }
+        issues
+    }
+}
+
+impl<NodeElement: Serialize + Passage> Graph<NodeElement, String> {
+    /// Read a Twine story from Twee 3 source. Every passage apart from StoryTitle and StoryData
+    /// becomes a Node, in the order they're written, and every link becomes an edge labelled with
+    /// its link text. The story title becomes the title of the Graph, and the Node for the start
+    /// passage is the root, with the story's IFID as its id so that exporting the Graph keeps it.
+    ///
+    /// Links that lead to passages that don't exist, or that have the same text as an earlier link
+    /// in the same passage, are left out and listed alongside the Graph.
+    pub fn from_twee(twee: &str) -> Result<(Self, Vec<TweeIssue>), TweeError> {
+        let mut passages = read_passages(twee);
+        let mut take = |name: &str| passages.iter()
+            .position(|passage| passage.name == name)
+            .map(|idx| passages.remove(idx));
+        let title = take(STORY_TITLE).map(|passage| passage.text);
+        let story_data = match take(STORY_DATA) {
+            Some(passage) => serde_json::from_str(&passage.text).map_err(|e| TweeError::InvalidStoryData(e.to_string()))?,
+            None => StoryData::default(),
+        };
+        if passages.is_empty() {
+            return Err(TweeError::NoPassages);
+        }
+        for (idx, passage) in passages.iter().enumerate() {
+            if passages[..idx].iter().any(|earlier| earlier.name == passage.name) {
+                return Err(TweeError::DuplicatePassage(passage.name.clone()));
+            }
+        }
+        let start_idx = match &story_data.start {
+            Some(start) => passages.iter()
+                .position(|passage| passage.name == *start)
+                .ok_or_else(|| TweeError::UnknownStart(start.clone()))?,
+            None => passages.iter().position(|passage| passage.name == DEFAULT_START).unwrap_or_default(),
+        };
+
+        let mut nodes = Vec::with_capacity(passages.len());
+        let mut passage_links = Vec::with_capacity(passages.len());
+        for (idx, passage) in passages.into_iter().enumerate() {
+            let (text, links) = read_links(&passage.text);
+            let mut node = Node::new(NodeElement::from_passage(passage.name.clone(), passage.tags, text));
+            if let Some(ifid) = story_data.ifid.as_ref().filter(|_| idx == start_idx) {
+                node.id = ifid.to_lowercase();
+            }
+            nodes.push((passage.name, node));
+            passage_links.push(links);
+        }
+        let mut issues = Vec::new();
+        for (idx, links) in passage_links.into_iter().enumerate() {
+            for (text, target) in links {
+                let target_id = match nodes.iter().find(|(name, _)| *name == target) {
+                    Some((_, target_node)) => target_node.id.clone(),
+                    None => {
+                        issues.push(TweeIssue::BrokenLink { passage: nodes[idx].0.clone(), target });
+                        continue;
+                    }
+                };
+                let (name, node) = &mut nodes[idx];
+                if node.edges.contains_key(&text) {
+                    issues.push(TweeIssue::DuplicateLink { passage: name.clone(), text });
+                    continue;
+                }
+                node.insert_edge(text, target_id);
+            }
+        }
+
+        let root_node_id = nodes[start_idx].1.id.clone();
+        let mut metadata = Metadata::new();
+        metadata.title = title;
+        let graph = Graph {
+            root_node_id: root_node_id.clone(),
+            current_node_id: root_node_id,
+            metadata,
+            nodes: nodes.into_iter()
+                .map(|(_, node)| (node.id.clone(), Rc::new(RefCell::new(node))))
+                .collect::<LinkedHashMap<_, _>>(),
+        };
+        Ok((graph, issues))
+    }
+}
+
+/// Exports Twee 3 source that Twine can import. Every Node is written as a passage with its name,
+/// tags and text, followed by a link for each of its edges. Twine doesn't allow spaces in tags, so
+/// they're written as dashes, and Nodes that share a name have a number added to keep the passage
+/// names apart. Twine can't escape anything in a link, so names and link text that would break one
+/// are changed to something similar that doesn't. The IFID that Twine needs is made from the id of
+/// the root Node.
+pub struct Twee;
+
+impl<NodeElement: Serialize + Passage, EdgeElement: Eq + Hash + Clone + Display> Exporter<NodeElement, EdgeElement> for Twee {
+    fn export(&self, graph: &Graph<NodeElement, EdgeElement>) -> String {
+        let mut names: Vec<(&str, String)> = Vec::with_capacity(graph.nodes.len());
+        for (node_id, node) in &graph.nodes {
+            let name = linkable(node.borrow().element.passage_name());
+            let mut unique_name = name.clone();
+            let mut count = 1;
+            while names.iter().any(|(_, taken)| *taken == unique_name) {
+                count += 1;
+                unique_name = format!("{} ({})", name, count);
+            }
+            names.push((node_id.as_str(), unique_name));
+        }
+        let name_of = |node_id: &str| names.iter()
+            .find(|(named_id, _)| *named_id == node_id)
+            .map(|(_, name)| name.as_str())
+            .unwrap_or_default();
+
+        let mut twee = String::new();
+        if let Some(title) = &graph.metadata.title {
+            writeln!(twee, "{} {}\n{}\n", PASSAGE_MARKER, STORY_TITLE, title).unwrap();
+        }
+        let story_data = StoryData {
+            ifid: Some(graph.root_node_id.to_uppercase()),
+            start: Some(name_of(&graph.root_node_id).to_string()),
+        };
+        let story_data = serde_json::to_string_pretty(&story_data).unwrap_or_default();
+        writeln!(twee, "{} {}\n{}\n", PASSAGE_MARKER, STORY_DATA, story_data).unwrap();
+
+        for (node_id, node) in &graph.nodes {
+            let node = node.borrow();
+            write!(twee, "{} {}", PASSAGE_MARKER, escape(name_of(node_id))).unwrap();
+            let tags = node.element.passage_tags().iter()
+                .map(|tag| escape(&dashed(tag)))
+                .collect::<Vec<String>>();
+            if !tags.is_empty() {
+                write!(twee, " [{}]", tags.join(" ")).unwrap();
+            }
+            twee.push('\n');
+            for line in node.element.passage_text().lines() {
+                if line.starts_with(PASSAGE_MARKER) {
+                    twee.push(ESCAPE);
+                }
+                writeln!(twee, "{}", line).unwrap();
+            }
+            let mut edges = node.edges().peekable();
+            if edges.peek().is_some() && !node.element.passage_text().is_empty() {
+                twee.push('\n');
+            }
+            for (edge, target_id) in edges {
+                let (text, target) = (linkable(&edge.to_string()), name_of(target_id));
+                match text == target {
+                    true => writeln!(twee, "[[{}]]", target).unwrap(),
+                    false => writeln!(twee, "[[{}->{}]]", text, target).unwrap(),
+                }
+            }
+            twee.push('\n');
+        }
+        twee
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_derive::Serialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize)]
+    struct Scene {
+        name: String,
+        tags: Vec<String>,
+        text: String,
+    }
+
+    impl Passage for Scene {
+        fn passage_name(&self) -> &str {
+            &self.name
+        }
+
+        fn passage_tags(&self) -> Vec<String> {
+            self.tags.clone()
+        }
+
+        fn passage_text(&self) -> &str {
+            &self.text
+        }
+
+        fn from_passage(name: String, tags: Vec<String>, text: String) -> Self {
+            Scene { name, tags, text }
+        }
+    }
+
+    /// The name of the Node that each edge from the named Node leads to.
+    fn links(graph: &Graph<Scene, String>, name: &str) -> Vec<(String, String)> {
+        let node = graph.nodes().find(|node| node.borrow().element.name == name).unwrap();
+        let node = node.borrow();
+        node.edges()
+            .map(|(edge, target_id)| (edge.clone(), graph.node(target_id).unwrap().borrow().element.name.clone()))
+            .collect()
+    }
+
+    const STORY: &str = r#":: StoryTitle
+The House
+
+:: StoryData
+{
+  "ifid": "D674C58C-DEFA-4F70-B7A2-27742230C0FC",
+  "start": "Hall"
+}
+
+:: Hall [indoors dusty]
+A dusty hall.
+
+[[north->Kitchen]]
+[[Cellar<-down]]
+[[Secret {Room}]]
+
+:: Kitchen [indoors]
+A cramped kitchen.
+
+[[south->Hall]]
+
+:: Cellar
+Dark and damp.
+
+[[up->Hall]]
+
+:: Secret \{Room\} {"position":"100,200"}
+\:: Nobody comes here.
+
+"#;
+
+    #[test]
+    fn passages_become_nodes_and_links_become_edges() {
+        let twee = "Ignored.\n:: Start [a\\]b]\nYou can go [[north|Kitchen]] or [[nowhere]].\n[[back->Start]] [[back->Kitchen]]\n:: Kitchen\nA kitchen.";
+        let (graph, issues) = Graph::<Scene, String>::from_twee(twee).unwrap();
+        let root = graph.node(graph.root_node_id()).unwrap();
+        assert_eq!(root.borrow().element, Scene {
+            name: "Start".to_string(),
+            tags: vec!["a]b".to_string()],
+            text: "You can go north or nowhere.".to_string(),
+        });
+        assert_eq!(links(&graph, "Start"), vec![
+            ("north".to_string(), "Kitchen".to_string()),
+            ("back".to_string(), "Start".to_string()),
+        ]);
+        assert_eq!(issues, vec![
+            TweeIssue::BrokenLink { passage: "Start".to_string(), target: "nowhere".to_string() },
+            TweeIssue::DuplicateLink { passage: "Start".to_string(), text: "back".to_string() },
+        ]);
+        assert_eq!(Graph::<Scene, String>::from_twee(":: A\n:: A\n").err(), Some(TweeError::DuplicatePassage("A".to_string())));
+        assert_eq!(Graph::<Scene, String>::from_twee(":: StoryTitle\nNothing\n").err(), Some(TweeError::NoPassages));
+    }
+
+    #[test]
+    fn stories_survive_a_round_trip() {
+        let (graph, issues) = Graph::<Scene, String>::from_twee(STORY).unwrap();
+        assert!(issues.is_empty(), "{:?}", issues);
+        assert_eq!(graph.metadata().title.as_deref(), Some("The House"));
+        assert_eq!(graph.root_node_id(), "d674c58c-defa-4f70-b7a2-27742230c0fc");
+        let hall = graph.node(graph.root_node_id()).unwrap();
+        assert_eq!(hall.borrow().element.tags, vec!["indoors".to_string(), "dusty".to_string()]);
+        assert_eq!(links(&graph, "Hall"), vec![
+            ("north".to_string(), "Kitchen".to_string()),
+            ("down".to_string(), "Cellar".to_string()),
+            ("Secret {Room}".to_string(), "Secret {Room}".to_string()),
+        ]);
+
+        let exported = Twee.export(&graph);
+        assert_eq!(exported, STORY
+            .replace("[[Cellar<-down]]", "[[down->Cellar]]")
+            .replace(r#" {"position":"100,200"}"#, ""));
+        let (reimported, _) = Graph::<Scene, String>::from_twee(&exported).unwrap();
+        assert_eq!(Twee.export(&reimported), exported);
+    }
+
+    #[test]
+    fn names_are_kept_apart_and_spaced_tags_are_reported() {
+        let mut graph = Graph::new(Scene { name: "Room".to_string(), tags: vec!["ground floor".to_string()], text: String::new() });
+        let other_room = Node::new(Scene { name: "Room".to_string(), tags: Vec::new(), text: String::new() });
+        let other_room_id = other_room.id.clone();
+        graph.insert_node(Rc::new(RefCell::new(other_room))).unwrap();
+        let root_node_id = graph.root_node_id().to_string();
+        graph.insert_edge(&root_node_id, "east".to_string(), other_room_id).unwrap();
+        let exported = Twee.export(&graph);
+        assert!(exported.ends_with(":: Room [ground-floor]\n[[east->Room (2)]]\n\n:: Room (2)\n\n"), "{}", exported);
+        assert!(exported.contains(&format!("\"ifid\": \"{}\"", root_node_id.to_uppercase())), "{}", exported);
+        assert_eq!(graph.twee_issues(), vec![TweeIssue::SpacedTag { passage: "Room".to_string(), tag: "ground floor".to_string() }]);
+    }
+
+    #[test]
+    fn names_that_would_break_links_are_renamed() {
+        let mut graph = Graph::new(Scene { name: "Start".to_string(), tags: Vec::new(), text: String::new() });
+        let root_node_id = graph.root_node_id().to_string();
+        for (edge, name) in [("in", "Room [A]"), ("Either|Or", "Either|Or"), ("<-back", "a->b")] {
+            let node = Node::new(Scene { name: name.to_string(), tags: Vec::new(), text: String::new() });
+            let node_id = node.id.clone();
+            graph.insert_node(Rc::new(RefCell::new(node))).unwrap();
+            graph.insert_edge(&root_node_id, edge.to_string(), node_id).unwrap();
+        }
+        let exported = Twee.export(&graph);
+        assert!(exported.contains("[[in->Room (A)]]\n[[Either/Or]]\n[[-back->a-b]]\n"), "{}", exported);
+        assert!(exported.contains("\n:: Room (A)\n"), "{}", exported);
+        let (reimported, issues) = Graph::<Scene, String>::from_twee(&exported).unwrap();
+        assert!(issues.is_empty(), "{:?}", issues);
+        assert_eq!(links(&reimported, "Start"), vec![
+            ("in".to_string(), "Room (A)".to_string()),
+            ("Either/Or".to_string(), "Either/Or".to_string()),
+            ("-back".to_string(), "a-b".to_string()),
+        ]);
+        assert_eq!(graph.twee_issues(), vec![
+            TweeIssue::RenamedLink { passage: "Start".to_string(), text: "Either|Or".to_string(), renamed: "Either/Or".to_string() },
+            TweeIssue::RenamedLink { passage: "Start".to_string(), text: "<-back".to_string(), renamed: "-back".to_string() },
+            TweeIssue::RenamedPassage { passage: "Room [A]".to_string(), renamed: "Room (A)".to_string() },
+            TweeIssue::RenamedPassage { passage: "Either|Or".to_string(), renamed: "Either/Or".to_string() },
+            TweeIssue::RenamedPassage { passage: "a->b".to_string(), renamed: "a-b".to_string() },
+        ]);
+    }
+}
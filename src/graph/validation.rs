@@ -0,0 +1,182 @@
+use std::fmt::{Display, Formatter};
+use std::hash::Hash;
+
+use serde::Serialize;
+
+use super::{Graph, GraphError};
+
+/// A structural problem found while loading or validating a Graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// There aren't any Nodes, so there isn't a root to start from.
+    EmptyGraph,
+    /// The Node for `node_id` has an edge leading to `target_id`, which isn't in the Graph.
+    DanglingEdge { node_id: String, target_id: String },
+    /// More than one Node in the data being loaded shares the provided id. Only the first one is
+    /// kept. A Graph can't hold two Nodes with the same id, so this is only reported when a Graph
+    /// is loaded, never by `Graph::validate`.
+    DuplicateId(String),
+    /// The Node for the provided id can't be reached by following edges from the root.
+    UnreachableNode(String),
+    /// The named item is placed in the Node for `node_id`, which isn't in the Graph, so it can never
+    /// be found.
+    MisplacedItem { item: String, node_id: String },
+    /// A condition is on an edge from the Node for `node_id` that isn't in the Graph.
+    ConditionOnMissingEdge { node_id: String, edge: String },
+    /// The condition on an edge from the Node for `node_id` requires something that can't happen.
+    UnmeetableCondition { node_id: String, edge: String },
+}
+
+impl ValidationIssue {
+    /// Whether the issue breaks the Graph, as opposed to being something worth warning about.
+    pub fn is_error(&self) -> bool {
+        matches!(self, ValidationIssue::EmptyGraph | ValidationIssue::DanglingEdge { .. } | ValidationIssue::DuplicateId(_))
+    }
+
+    /// The error that the issue causes when a Graph is validated strictly, if it's an error.
+    pub fn to_error(&self) -> Option<GraphError> {
+        match self {
+            ValidationIssue::EmptyGraph => Some(GraphError::EmptyGraph),
+            ValidationIssue::DanglingEdge { node_id, target_id } =>
+                Some(GraphError::DanglingEdge { node_id: node_id.clone(), target_id: target_id.clone() }),
+            ValidationIssue::DuplicateId(node_id) => Some(GraphError::DuplicateId(node_id.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationIssue::EmptyGraph => write!(f, "The graph doesn't have any nodes."),
+            ValidationIssue::DanglingEdge { node_id, target_id } =>
+                write!(f, "The node with the id {} has an edge to {}, which doesn't exist.", node_id, target_id),
+            ValidationIssue::DuplicateId(node_id) => write!(f, "More than one node has the id {}.", node_id),
+            ValidationIssue::UnreachableNode(node_id) =>
+                write!(f, "The node with the id {} can't be reached from the root.", node_id),
+            ValidationIssue::MisplacedItem { item, node_id } =>
+                write!(f, "The item \"{}\" is in the node with the id {}, which doesn't exist.", item, node_id),
+            ValidationIssue::ConditionOnMissingEdge { node_id, edge } =>
+                write!(f, "The node with the id {} has a condition on \"{}\", which isn't one of its edges.", node_id, edge),
+            ValidationIssue::UnmeetableCondition { node_id, edge } =>
+                write!(f, "The condition on \"{}\" from the node with the id {} can never be met.", edge, node_id),
+        }
+    }
+}
+
+/// Every issue found while validating a Graph.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Record another issue.
+    pub fn push(&mut self, issue: ValidationIssue) {
+        self.issues.push(issue);
+    }
+
+    /// Returns an Iterator over all of the issues in the order they were found.
+    pub fn issues(&self) -> impl Iterator<Item=&ValidationIssue> + '_ {
+        self.issues.iter()
+    }
+
+    /// Whether no issues were found at all.
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// The error for the first issue that breaks the Graph, if there is one.
+    pub fn first_error(&self) -> Option<GraphError> {
+        self.issues.iter().find_map(|issue| issue.to_error())
+    }
+}
+
+/// How issues found while loading a Graph are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationMode {
+    /// Refuse to load a Graph that has any errors.
+    Strict,
+    /// Load whatever can be loaded and report the errors alongside the Graph.
+    Lenient,
+}
+
+impl<NodeElement: Serialize, EdgeElement: Eq + Hash + Clone> Graph<NodeElement, EdgeElement> {
+    /// Check the Graph for dangling edges and Nodes that can't be reached from the root. Duplicate
+    /// ids are only found while loading, since the Graph keeps its Nodes by id.
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        if self.nodes.is_empty() {
+            report.push(ValidationIssue::EmptyGraph);
+            return report;
+        }
+        for (node_id, node) in &self.nodes {
+            for (_, target_id) in node.borrow().edges.iter() {
+                if !self.nodes.contains_key(target_id) {
+                    report.push(ValidationIssue::DanglingEdge { node_id: node_id.clone(), target_id: target_id.clone() });
+                }
+            }
+        }
+        for node_id in self.unreachable_nodes() {
+            report.push(ValidationIssue::UnreachableNode(node_id));
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{Metadata, Node};
+    use crate::graph::test_support::map;
+
+    /// A hall that leads north to a kitchen and east to a Node that doesn't exist, plus a second
+    /// Node with the kitchen's id.
+    fn broken_nodes() -> (Vec<Node<String, String>>, String, String) {
+        let mut hall = Node::new("Hall".to_string());
+        let mut kitchen = Node::new("Kitchen".to_string());
+        let mut impostor = Node::new("Impostor".to_string());
+        impostor.id = kitchen.id.clone();
+        hall.insert_edge("north".to_string(), kitchen.id.clone());
+        hall.insert_edge("east".to_string(), "missing".to_string());
+        kitchen.insert_edge("south".to_string(), hall.id.clone());
+        let (hall_id, kitchen_id) = (hall.id.clone(), kitchen.id.clone());
+        (vec![hall, kitchen, impostor], hall_id, kitchen_id)
+    }
+
+    #[test]
+    fn dangling_edges_are_errors_and_unreachable_nodes_are_warnings() {
+        let (graph, node_ids) = map(&["Hall", "Kitchen", "Attic"], &[(0, "north", 1)]);
+        graph.node(&node_ids[1]).unwrap().borrow_mut().insert_edge("up".to_string(), "missing".to_string());
+        let report = graph.validate();
+        assert_eq!(report.issues().cloned().collect::<Vec<ValidationIssue>>(), vec![
+            ValidationIssue::DanglingEdge { node_id: node_ids[1].clone(), target_id: "missing".to_string() },
+            ValidationIssue::UnreachableNode(node_ids[2].clone()),
+        ]);
+        assert!(!ValidationIssue::UnreachableNode(node_ids[2].clone()).is_error());
+        assert_eq!(report.first_error(), Some(GraphError::DanglingEdge { node_id: node_ids[1].clone(), target_id: "missing".to_string() }));
+        assert!(map(&["Hall"], &[]).0.validate().is_empty());
+    }
+
+    #[test]
+    fn strict_mode_refuses_errors() {
+        let (nodes, hall_id, _) = broken_nodes();
+        let result = Graph::from_parts(hall_id.clone(), hall_id, Metadata::new(), nodes, ValidationMode::Strict);
+        assert!(matches!(result, Err(GraphError::DuplicateId(_))), "{:?}", result.map(|(_, report)| report));
+        let result = Graph::<String, String>::from_parts("a".to_string(), "a".to_string(), Metadata::new(), Vec::new(), ValidationMode::Lenient);
+        assert!(matches!(result, Err(GraphError::EmptyGraph)));
+    }
+
+    #[test]
+    fn lenient_mode_keeps_the_first_node_for_an_id_and_drops_dangling_edges() {
+        let (nodes, hall_id, kitchen_id) = broken_nodes();
+        let (graph, report) = Graph::from_parts(hall_id.clone(), hall_id.clone(), Metadata::new(), nodes, ValidationMode::Lenient).unwrap();
+        assert_eq!(report.issues().cloned().collect::<Vec<ValidationIssue>>(), vec![
+            ValidationIssue::DuplicateId(kitchen_id.clone()),
+            ValidationIssue::DanglingEdge { node_id: hall_id.clone(), target_id: "missing".to_string() },
+        ]);
+        assert_eq!(graph.node(&kitchen_id).unwrap().borrow().element, "Kitchen");
+        assert_eq!(graph.node(&hall_id).unwrap().borrow().edge_elements().collect::<Vec<String>>(), vec!["north".to_string()]);
+        assert!(graph.validate().is_empty());
+    }
+}
@@ -0,0 +1,41 @@
+use serde_derive::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Where an item is.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemLocation {
+    /// Lying in the location for the provided node id.
+    Node(String),
+    /// Being carried by the player.
+    Inventory,
+}
+
+/// An object that can be found in a location and carried around by the player.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Item {
+    /// Uniquely identifies this item.
+    pub id: String,
+    /// The short name that the player uses to refer to the item.
+    pub name: String,
+    /// What the player is told when they examine the item.
+    pub description: String,
+    /// Where the item is when a new game starts.
+    pub location: ItemLocation,
+}
+
+impl Item {
+    pub fn new(name: String, description: String, location: ItemLocation) -> Self {
+        Item {
+            id: Uuid::new_v4().to_string(),
+            name,
+            description,
+            location,
+        }
+    }
+
+    /// Whether the player is referring to this item by the provided name.
+    pub fn is_called(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name.trim())
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::graph::{Graph, Node, Passage, Playable, Regional};
+
+/// Starts the tag that puts a location in a region, as in "region:Cellar".
+const REGION_TAG_PREFIX: &str = "region:";
+
+/// A map of Locations joined by the directions between them.
+pub type LocationGraph = Graph<Location, String>;
+pub type LocationNode = Node<Location, String>;
+
+/// A place in a map that the player can be in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Location {
+    /// A short name for the location, used in lists and when the player walks through it.
+    pub name: String,
+    /// What the player is told when they're at the location.
+    pub description: String,
+    /// What the player is told instead of the description the first time they arrive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_visit_description: Option<String>,
+    /// Labels that the author can use to group locations, such as the region they're in.
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    /// Arbitrary values that the author wants to keep with the location.
+    #[serde(default)]
+    pub properties: BTreeMap<String, String>,
+    /// The flags that are set in a game when the player arrives at the location, which conditions on
+    /// directions can require.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub sets_flags: BTreeSet<String>,
+}
+
+impl Location {
+    pub fn new(name: String, description: String) -> Self {
+        Location {
+            name,
+            description,
+            first_visit_description: None,
+            tags: BTreeSet::new(),
+            properties: BTreeMap::new(),
+            sets_flags: BTreeSet::new(),
+        }
+    }
+
+    /// What the player is told when they're at the location, depending on whether they've just
+    /// arrived for the first time.
+    pub fn description_for_visit(&self, first_visit: bool) -> &str {
+        match &self.first_visit_description {
+            Some(first_visit_description) if first_visit => first_visit_description,
+            _ => &self.description,
+        }
+    }
+
+    /// Whether the name or description contains the text, ignoring case.
+    pub fn matches(&self, text: &str) -> bool {
+        let text = text.to_lowercase();
+        self.name.to_lowercase().contains(&text) || self.description.to_lowercase().contains(&text)
+    }
+}
+
+/// A location is in the region named by its first "region:" tag.
+impl Regional for Location {
+    fn region(&self) -> Option<&str> {
+        self.tags.iter().find_map(|tag| tag.strip_prefix(REGION_TAG_PREFIX)).map(str::trim)
+    }
+}
+
+/// A location is written as a passage with the same name, tags and description. Anything else about
+/// it is left out.
+impl Passage for Location {
+    fn passage_name(&self) -> &str {
+        &self.name
+    }
+
+    fn passage_tags(&self) -> Vec<String> {
+        self.tags.iter().cloned().collect()
+    }
+
+    fn passage_text(&self) -> &str {
+        &self.description
+    }
+
+    fn from_passage(name: String, tags: Vec<String>, text: String) -> Self {
+        let mut location = Location::new(name, text);
+        location.tags = tags.into_iter().collect();
+        location
+    }
+}
+
+impl Playable for Location {
+    fn description(&self, first_visit: bool) -> &str {
+        self.description_for_visit(first_visit)
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
@@ -3,221 +3,1689 @@
  */
 
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::env;
 use std::fs;
-use std::io::{stdin, stdout, Write};
+use std::path::Path;
+use std::process;
+use std::rc::Rc;
 
-use crate::graph::Graph;
+use serde::Deserialize;
+use serde_derive::{Deserialize, Serialize};
 
+use crate::condition::{EdgeCondition, Requirement};
+use crate::console::{Console, StdConsole};
+use crate::graph::{Direction, DotOptions, ExportFormat, Exporter, FORMAT_VERSION, GraphError, GraphMl, History, Html, Mermaid, Node, Operation, Reversible, Twee, Undoable, ValidationIssue, ValidationMode, ValidationReport};
+use crate::item::{Item, ItemLocation};
+use crate::location::{Location, LocationGraph, LocationNode};
+use crate::parser::{Parser, Verb};
+use crate::save_game::SaveGame;
+
+mod cli;
+mod condition;
+mod console;
 mod graph;
+mod item;
+mod location;
+mod migration;
+mod parser;
+mod save_game;
+mod storage;
+mod transcript;
 
 const PROMPT: &str = ">";
 
-fn prompt(prompt: &str) -> String {
-    print!("{} ", prompt);
-    let _ = stdout().flush().expect("Failure flushing stdout");
-    let mut input = String::new();
-    let _ = stdin().read_line(&mut input).expect("Failure getting STDIN.");
-    return input.trim().to_string();
+/// How many steps away the map of nearby locations goes when it isn't given.
+const DEFAULT_MAP_DISTANCE: usize = 2;
+
+/// Ask for a line of input, returning None when there isn't any more. Whatever was asking for it
+/// gives up, so that the menus and the game end the same as when the user exits them.
+fn prompt(console: &mut dyn Console, prompt_text: &str) -> Option<String> {
+    write!(console, "{} ", prompt_text);
+    console.read_line().map(|input| input.trim().to_string())
 }
 
-fn prompt_with_options(prompt_text: &str, options: Vec<&str>) -> String {
+fn prompt_with_options(console: &mut dyn Console, prompt_text: &str, options: Vec<&str>) -> Option<String> {
     loop {
-        let input = prompt(prompt_text);
+        let input = prompt(console, prompt_text)?;
         if options.contains(&input.as_str()) {
-            return input;
+            return Some(input);
         }
-        println!("Invalid input. Give it another go...");
+        writeln!(console, "Invalid input. Give it another go...");
     }
 }
 
-type StringGraph = Graph<String, String>;
+fn report(console: &mut dyn Console, result: Result<(), GraphError>) {
+    if let Err(e) = result {
+        writeln!(console, "An error occurred: {}", e);
+    }
+}
 
-fn print_current_location(graph: &StringGraph) {
+fn print_location<'a>(console: &mut dyn Console, location: &LocationNode, first_visit: bool, items: impl Iterator<Item=&'a Item>) {
     // Borrowing example
     // https://www.reddit.com/r/rust/comments/6q4uqc/help_whats_the_best_way_to_join_an_iterator_of/
-    let current_node = &graph.current_node();
-    let location = current_node.borrow();
     let possible_directions =
         location.edge_elements().collect::<Vec<String>>().join(", ");
-    println!(r#"
-Current Location:
+    writeln!(console, r#"
+Current Location: {}
     Description: {}
     Possible Directions: {}"#
-             , location.element, possible_directions);
+             , location.element.name, location.element.description_for_visit(first_visit), possible_directions);
+    let item_names = items.map(|item| item.name.as_str()).collect::<Vec<&str>>();
+    if !item_names.is_empty() {
+        writeln!(console, "    Items: {}", item_names.join(", "));
+    }
 }
 
-fn main_menu() -> String {
-    println!(r#"
+/// Every item that the map places in the location for the provided node id.
+fn items_placed_at<'a>(items: &'a [Item], node_id: &'a str) -> impl Iterator<Item=&'a Item> + 'a {
+    items.iter().filter(move |item| matches!(&item.location, ItemLocation::Node(item_node_id) if item_node_id == node_id))
+}
+
+fn print_current_location(console: &mut dyn Console, graph_file: &GraphFile) -> Result<(), GraphError> {
+    let current_node = graph_file.graph.current_node()?;
+    let current_node = current_node.borrow();
+    print_location(console, &current_node, false, items_placed_at(&graph_file.items, &current_node.id));
+    let location = &current_node.element;
+    if let Some(first_visit_description) = &location.first_visit_description {
+        writeln!(console, "    First Visit Description: {}", first_visit_description);
+    }
+    if let Some(position) = current_node.position {
+        writeln!(console, "    Position: {}", position);
+    }
+    if !location.tags.is_empty() {
+        writeln!(console, "    Tags: {}", location.tags.iter().map(String::as_str).collect::<Vec<&str>>().join(", "));
+    }
+    for (key, value) in &location.properties {
+        writeln!(console, "    Property: {} = {}", key, value);
+    }
+    if !location.sets_flags.is_empty() {
+        writeln!(console, "    Sets Flags: {}", location.sets_flags.iter().map(String::as_str).collect::<Vec<&str>>().join(", "));
+    }
+    let conditions = graph_file.conditions.iter()
+        .filter(|condition| condition.node_id == current_node.id)
+        .collect::<Vec<&EdgeCondition>>();
+    if !conditions.is_empty() {
+        writeln!(console, "    Conditions:");
+        for condition in conditions {
+            writeln!(console, "        {}: requires {}", condition.edge, describe_requirement(graph_file, &condition.requirement));
+        }
+    }
+    Ok(())
+}
+
+fn describe_requirement(graph_file: &GraphFile, requirement: &Requirement) -> String {
+    match requirement {
+        Requirement::HoldsItem { item_id } => match graph_file.items.iter().find(|item| &item.id == item_id) {
+            Some(item) => format!("holding the {}", item.name),
+            None => "holding an item that isn't in the map anymore".to_string(),
+        },
+        Requirement::FlagSet { flag } => format!("the \"{}\" flag to be set", flag),
+        Requirement::Visited { node_id } => match graph_file.graph.node(node_id) {
+            Ok(node) => format!("having visited {}", node.borrow().element),
+            Err(_) => "having visited a location that isn't in the map anymore".to_string(),
+        },
+    }
+}
+
+fn main_menu(console: &mut dyn Console) -> Option<String> {
+    writeln!(console, r#"
 1. New Map
 2. Load Existing Map
+3. Import a Twine Story
 x. Exit"#);
-    return prompt_with_options(PROMPT, vec!["1", "2", "x"]);
+    prompt_with_options(console, PROMPT, vec!["1", "2", "3", "x"])
 }
 
-fn location_edit_menu(graph: &StringGraph) -> String {
-    print_current_location(graph);
-    println!(r#"
-1. Update description.
+fn location_edit_menu(console: &mut dyn Console, graph_file: &GraphFile) -> Option<String> {
+    if let Some(title) = &graph_file.graph.metadata().title {
+        writeln!(console, "\n{}", title);
+    }
+    let result = print_current_location(console, graph_file);
+    report(console, result);
+    writeln!(console, r#"
+1. Update location.
 2. Connect location.
 3. Move
 4. Enter interactive mode
 5. Reset to root node.
+6. Delete location.
+7. Remove direction.
+8. Change where a direction leads.
+9. Validate map.
+10. Update map details.
+11. Map report.
+12. Undo.
+13. Redo.
+14. Place a new item here.
+15. Remove an item from here.
+16. Add or change the condition on a direction.
+17. Remove the condition from a direction.
+18. Export the map as DOT, Mermaid, GraphML, Twee or a playable web page.
+19. Show a map of the locations nearby.
+20. Lay out the map from its compass directions.
 x. Back to the main menu"#);
-    return prompt_with_options(PROMPT, vec!["1", "2", "3", "4", "5", "x"]);
+    prompt_with_options(console, PROMPT, vec!["1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15", "16", "17", "18", "19", "20", "x"])
+}
+
+fn current_node_id(graph: &LocationGraph) -> Result<String, GraphError> {
+    Ok(graph.current_node()?.borrow().id.clone())
 }
 
-fn update_location_description(graph: &mut StringGraph) {
-    println!("Current description:\n{}\n", &graph.current_node().borrow().element);
-    {
-        let current_node = &graph.current_node();
-        let mut borrowed_node = current_node.borrow_mut();
-        borrowed_node.element = prompt("Enter the new description of your first node:");
+fn update_location(console: &mut dyn Console, graph_file: &mut GraphFile) -> Result<(), GraphError> {
+    let graph = &graph_file.graph;
+    let mut location = graph.current_node()?.borrow().element.clone();
+    // The location is left as it was when the input runs out part way through.
+    if prompt_to_update_location(console, &mut location).is_none() || location == graph.current_node()?.borrow().element {
+        return Ok(());
     }
+    let node_id = current_node_id(graph)?;
+    graph_file.apply(Operation::SetElement { node_id, element: location })
 }
 
-fn connect_location(graph: &mut StringGraph) {
-    let new_direction = prompt("Enter the direction that will take you to the new location:");
+/// Ask for a change to each part of the location. Returns None when the input runs out.
+fn prompt_to_update_location(console: &mut dyn Console, location: &mut Location) -> Option<()> {
+    prompt_to_update_text(console, "Enter the name of the location", &mut location.name)?;
+    prompt_to_update_text(console, "Enter the description of the location", &mut location.description)?;
+    writeln!(console, "Enter a single dash to clear any of the next three.");
+    match prompt_for_change(console, "Enter the description for the player's first visit", location.first_visit_description.as_deref().unwrap_or(""))? {
+        Some(input) if input == "-" => location.first_visit_description = None,
+        Some(input) => location.first_visit_description = Some(input),
+        None => (),
+    }
+    prompt_to_update_list(console, "Enter the tags, separated by commas", &mut location.tags)?;
+    prompt_to_update_list(console, "Enter the flags that arriving sets, separated by commas", &mut location.sets_flags)?;
+    update_properties(console, location)
+}
 
-    // TODO: New or existing location?
+fn update_properties(console: &mut dyn Console, location: &mut Location) -> Option<()> {
     loop {
-        match prompt_with_options("Create a new location or use an existing location?", vec!["N", "n", "E", "e"]).as_str() {
-            "n" | "N" => {
-                graph.insert_edge_to_new_node(new_direction.clone(), prompt("Enter the description for the new location:"));
-                break;
-            }
-            "e" | "E" => {
-                graph.insert_edge_to_existing_node(new_direction.clone(), select_node(graph));
-                break;
+        let input = prompt(console, "Enter a property as key=value, key= to remove one, or leave blank to finish:")?;
+        if input.is_empty() {
+            return Some(());
+        }
+        match input.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                if value.trim().is_empty() {
+                    location.properties.remove(key.trim());
+                } else {
+                    location.properties.insert(key.trim().to_string(), value.trim().to_string());
+                }
             }
-            _ => (),
+            _ => writeln!(console, "Invalid input. Give it another go..."),
         }
     }
-    let original_node = graph.current_node();
+}
 
-    graph.traverse(new_direction);
-    loop {
-        match prompt_with_options("Do you want to be able to get back to the original location (Y/N)?", vec!["y", "Y", "n", "N"]).as_str() {
-            "y" | "Y" => {
-                let return_direction = prompt("Enter the return direction that will take you back:");
-                let original_node_borrowed = original_node.borrow();
-                graph.current_node().borrow_mut().insert_edge(return_direction, original_node_borrowed.id.clone());
-                break;
-            }
-            "n" | "N" => break,
+fn prompt_for_location(console: &mut dyn Console) -> Option<Location> {
+    let name = prompt(console, "Enter the name of the location:")?;
+    let description = prompt(console, "Enter the description of the location:")?;
+    Some(Location::new(name, description))
+}
+
+fn connect_location(console: &mut dyn Console, graph_file: &mut GraphFile) -> Result<(), GraphError> {
+    let new_direction = match prompt(console, "Enter the direction that will take you to the new location:") {
+        Some(new_direction) => new_direction,
+        None => return Ok(()),
+    };
+    // Built-in directions are always written out in full, so "ne" is stored as "northeast".
+    let new_direction = new_direction.parse::<Direction>().map_or(new_direction, |direction| direction.to_string());
+    let original_node_id = current_node_id(&graph_file.graph)?;
+    // Nothing is connected when the input runs out part way through.
+    let operations = match prompt_for_connection(console, &graph_file.graph, original_node_id, &new_direction) {
+        Some(operations) => operations,
+        None => return Ok(()),
+    };
+    graph_file.apply(Operation::Batch(operations))?;
+    graph_file.graph.traverse(new_direction)?;
+    Ok(())
+}
+
+/// Ask where the new direction leads and how to get back, returning the Operations that connect
+/// it, or None when the input runs out. Every change is collected so that connecting a location can
+/// be undone in one step.
+fn prompt_for_connection(console: &mut dyn Console, graph: &LocationGraph, original_node_id: String, new_direction: &str) -> Option<Vec<Operation<Location, String>>> {
+    let new_direction = new_direction.to_string();
+    let mut operations = Vec::new();
+    let target_node_id = loop {
+        match prompt_with_options(console, "Create a new location or use an existing location?", vec!["N", "n", "E", "e"])?.as_str() {
+            "n" | "N" => {
+                let new_node = Node::new(prompt_for_location(console)?);
+                let new_node_id = new_node.id.clone();
+                operations.push(Operation::AddNode { node: Rc::new(RefCell::new(new_node)) });
+                break new_node_id;
+            }
+            "e" | "E" => break select_node(console, graph)?,
             _ => (),
         }
+    };
+    // Built-in directions have an opposite that can be used as the way back without typing it.
+    let way_back = match new_direction.reverse() {
+        Some(reverse_direction) => {
+            let prompt_text = format!("Use \"{}\" as the way back (Y), enter a different way back (D) or don't add one (N)?", reverse_direction);
+            prompt_with_options(console, &prompt_text, vec!["y", "Y", "d", "D", "n", "N"])?
+        }
+        None => match prompt_with_options(console, "Do you want to be able to get back to the original location (Y/N)?", vec!["y", "Y", "n", "N"])?.as_str() {
+            "y" | "Y" => "D".to_string(),
+            _ => "N".to_string(),
+        },
+    };
+    match way_back.as_str() {
+        "y" | "Y" => operations.push(Operation::LinkBothWays {
+            node_id: original_node_id,
+            edge: new_direction.clone(),
+            target_id: target_node_id,
+        }),
+        "d" | "D" => {
+            operations.push(Operation::AddEdge {
+                node_id: original_node_id.clone(),
+                edge: new_direction.clone(),
+                target_id: target_node_id.clone(),
+            });
+            operations.push(Operation::AddEdge {
+                node_id: target_node_id,
+                edge: prompt(console, "Enter the return direction that will take you back:")?,
+                target_id: original_node_id,
+            });
+        }
+        _ => operations.push(Operation::AddEdge {
+            node_id: original_node_id,
+            edge: new_direction,
+            target_id: target_node_id,
+        }),
     }
+    Some(operations)
 }
 
-fn select_node(graph: &StringGraph) -> String {
+fn select_node(console: &mut dyn Console, graph: &LocationGraph) -> Option<String> {
     let mut idx: u32 = 0;
     let mut node_id_index: HashMap<u32, String> = HashMap::new();
     for node in graph.nodes() {
         idx += 1;
         let borrowed_node = node.borrow();
         node_id_index.insert(idx, borrowed_node.id.clone());
-        println!("{}. {}", idx, borrowed_node.element);
+        writeln!(console, "{}. {}", idx, borrowed_node.element);
     }
 
     loop {
         // TODO: Handle cancel option.
-        match prompt("Enter the location number:").parse::<u32>() {
-            Ok(selected_idx) => {
-                match node_id_index.get(&selected_idx) {
-                    Some(node_id) => return node_id.clone(),
-                    None => (),
+        if let Ok(selected_idx) = prompt(console, "Enter the location number:")?.parse::<u32>() {
+            if let Some(node_id) = node_id_index.get(&selected_idx) {
+                return Some(node_id.clone());
+            }
+        }
+        writeln!(console, "Invalid input. Give it another go...")
+    }
+}
+
+/// Delete a location along with every direction leading to it and the items that are placed in it.
+fn delete_location(console: &mut dyn Console, graph_file: &mut GraphFile) -> Result<(), GraphError> {
+    let node_id = match select_node(console, &graph_file.graph) {
+        Some(node_id) => node_id,
+        None => return Ok(()),
+    };
+    let item_names = items_placed_at(&graph_file.items, &node_id)
+        .map(|item| item.name.clone())
+        .collect::<Vec<String>>();
+    // Items are removed from the end first so that the positions of the rest stay the same.
+    let mut operations = vec![MapOperation::Graph(Operation::RemoveNode { node_id: node_id.clone() })];
+    operations.extend(graph_file.items.iter()
+        .enumerate()
+        .filter(|(_, item)| item.location == ItemLocation::Node(node_id.clone()))
+        .map(|(index, _)| MapOperation::RemoveItem { index })
+        .rev());
+    let removed_conditions = change_removing_stale_conditions(graph_file, operations)?;
+    writeln!(console, "The location and every direction leading to it have been deleted.");
+    if !item_names.is_empty() {
+        writeln!(console, "The items in it were deleted too: {}", item_names.join(", "));
+    }
+    report_removed_conditions(console, removed_conditions);
+    Ok(())
+}
+
+/// Make the changes along with removing every condition that they leave over, all in a single step
+/// that can be undone. Returns how many conditions were removed.
+fn change_removing_stale_conditions(graph_file: &mut GraphFile, mut operations: Vec<MapOperation>) -> Result<usize, GraphError> {
+    let count = graph_file.conditions.len();
+    operations.push(MapOperation::RemoveStaleConditions);
+    graph_file.change(MapOperation::Batch(operations))?;
+    Ok(count - graph_file.conditions.len())
+}
+
+fn report_removed_conditions(console: &mut dyn Console, removed_conditions: usize) {
+    match removed_conditions {
+        0 => (),
+        1 => writeln!(console, "A condition that depended on it was removed as well."),
+        _ => writeln!(console, "{} conditions that depended on it were removed as well.", removed_conditions),
+    }
+}
+
+fn select_direction(console: &mut dyn Console, graph: &LocationGraph, prompt_text: &str) -> Result<Option<String>, GraphError> {
+    let directions = graph.current_node()?.borrow().edge_elements().collect::<Vec<String>>();
+    if directions.is_empty() {
+        writeln!(console, "There aren't any directions from this location.");
+        return Ok(None);
+    }
+    Ok(prompt_with_options(console, prompt_text, directions.iter().map(|direction| direction.as_str()).collect()))
+}
+
+fn remove_direction(console: &mut dyn Console, graph_file: &mut GraphFile) -> Result<(), GraphError> {
+    if let Some(direction) = select_direction(console, &graph_file.graph, "Enter the direction to remove:")? {
+        let node_id = current_node_id(&graph_file.graph)?;
+        let operation = MapOperation::Graph(Operation::RemoveEdge { node_id, edge: direction });
+        let removed_conditions = change_removing_stale_conditions(graph_file, vec![operation])?;
+        report_removed_conditions(console, removed_conditions);
+    }
+    Ok(())
+}
+
+fn retarget_direction(console: &mut dyn Console, graph_file: &mut GraphFile) -> Result<(), GraphError> {
+    if let Some(direction) = select_direction(console, &graph_file.graph, "Enter the direction to change:")? {
+        let node_id = current_node_id(&graph_file.graph)?;
+        let target_id = match select_node(console, &graph_file.graph) {
+            Some(target_id) => target_id,
+            None => return Ok(()),
+        };
+        // A condition stays with its direction, but it may not make sense for the new location.
+        let keep_condition = match condition::condition_for(&graph_file.conditions, &node_id, &direction) {
+            Some(condition) => {
+                let prompt_text = format!("Going {} requires {}. Keep the condition (Y/N)?", direction, describe_requirement(graph_file, &condition.requirement));
+                match prompt_with_options(console, &prompt_text, vec!["y", "Y", "n", "N"]) {
+                    Some(input) => matches!(input.as_str(), "y" | "Y"),
+                    None => return Ok(()),
                 }
             }
-            Err(_) => ()
+            None => true,
+        };
+        let mut operations = vec![MapOperation::Graph(Operation::RetargetEdge { node_id: node_id.clone(), edge: direction.clone(), target_id })];
+        if let Some(index) = condition::position_of(&graph_file.conditions, &node_id, &direction).filter(|_| !keep_condition) {
+            operations.push(MapOperation::RemoveCondition { index });
         }
-        println!("Invalid input. Give it another go...")
+        graph_file.change(MapOperation::Batch(operations))?;
+    }
+    Ok(())
+}
+
+/// Give every location that compass directions lead to a position, then list anything that didn't
+/// fit. Locations that can't be reached keep the position they had.
+fn lay_out_map(console: &mut dyn Console, graph_file: &mut GraphFile) -> Result<(), GraphError> {
+    let layout = graph_file.graph.auto_layout();
+    let graph = &graph_file.graph;
+    let operations = layout.positions.into_iter()
+        .filter(|(node_id, position)| graph.node(node_id).map_or(true, |node| node.borrow().position != Some(*position)))
+        .map(|(node_id, position)| Operation::SetPosition { node_id, position: Some(position) })
+        .collect::<Vec<_>>();
+    // Nothing is recorded to undo when every location is already where it belongs.
+    if !operations.is_empty() {
+        graph_file.apply(Operation::Batch(operations))?;
+    }
+    if layout.issues.is_empty() {
+        writeln!(console, "Every location has been laid out.");
+    }
+    for issue in layout.issues {
+        writeln!(console, "Warning: {}", issue);
+    }
+    Ok(())
+}
+
+fn place_item(console: &mut dyn Console, graph_file: &mut GraphFile) -> Result<(), GraphError> {
+    let location = ItemLocation::Node(current_node_id(&graph_file.graph)?);
+    let item = prompt(console, "Enter the name of the item:").and_then(|name| {
+        prompt(console, "Enter the description of the item:").map(|description| Item::new(name, description, location))
+    });
+    match item {
+        Some(item) => graph_file.change(MapOperation::InsertItem { index: graph_file.items.len(), item }),
+        None => Ok(()),
     }
 }
 
-fn move_to_location(graph: &mut StringGraph) -> bool {
+fn remove_item(console: &mut dyn Console, graph_file: &mut GraphFile) -> Result<(), GraphError> {
+    let node_id = current_node_id(&graph_file.graph)?;
+    let names = items_placed_at(&graph_file.items, &node_id)
+        .map(|item| item.name.clone())
+        .collect::<Vec<String>>();
+    if names.is_empty() {
+        writeln!(console, "There aren't any items here.");
+        return Ok(());
+    }
+    let name = match prompt_with_options(console, "Enter the name of the item to remove:", names.iter().map(|name| name.as_str()).collect()) {
+        Some(name) => name,
+        None => return Ok(()),
+    };
+    let operations = graph_file.items.iter()
+        .enumerate()
+        .filter(|(_, item)| item.name == name && item.location == ItemLocation::Node(node_id.clone()))
+        .map(|(index, _)| MapOperation::RemoveItem { index })
+        .rev()
+        .collect();
+    let removed_conditions = change_removing_stale_conditions(graph_file, operations)?;
+    report_removed_conditions(console, removed_conditions);
+    Ok(())
+}
+
+fn select_item(console: &mut dyn Console, items: &[Item]) -> Option<String> {
+    if items.is_empty() {
+        writeln!(console, "There aren't any items in the map.");
+        return None;
+    }
+    for (idx, item) in items.iter().enumerate() {
+        writeln!(console, "{}. {}", idx + 1, item.name);
+    }
     loop {
-        let desired_direction = prompt("Which way do you want to go? ");
-        if desired_direction.to_uppercase() == "X".to_string() {
-            return false;
+        if let Ok(selected_idx) = prompt(console, "Enter the item number:")?.parse::<usize>() {
+            if let Some(item) = selected_idx.checked_sub(1).and_then(|idx| items.get(idx)) {
+                return Some(item.id.clone());
+            }
         }
+        writeln!(console, "Invalid input. Give it another go...")
+    }
+}
+
+fn select_requirement(console: &mut dyn Console, graph_file: &GraphFile) -> Option<Requirement> {
+    match prompt_with_options(console, "Require holding an item (I), a flag being set (F) or having visited a location (V)?", vec!["i", "I", "f", "F", "v", "V"])?.as_str() {
+        "i" | "I" => select_item(console, &graph_file.items).map(|item_id| Requirement::HoldsItem { item_id }),
+        "f" | "F" => Some(Requirement::FlagSet { flag: prompt(console, "Enter the name of the flag:")? }),
+        _ => Some(Requirement::Visited { node_id: select_node(console, &graph_file.graph)? }),
+    }
+}
 
+/// Attach a condition to a direction from the current location, replacing any condition that's
+/// already there.
+fn edit_condition(console: &mut dyn Console, graph_file: &mut GraphFile) -> Result<(), GraphError> {
+    let edge = match select_direction(console, &graph_file.graph, "Enter the direction to put a condition on:")? {
+        Some(edge) => edge,
+        None => return Ok(()),
+    };
+    let node_id = current_node_id(&graph_file.graph)?;
+    if let Some(condition) = condition::condition_for(&graph_file.conditions, &node_id, &edge) {
+        writeln!(console, "Going {} currently requires {}.", edge, describe_requirement(graph_file, &condition.requirement));
+    }
+    let requirement = match select_requirement(console, graph_file) {
+        Some(requirement) => requirement,
+        None => return Ok(()),
+    };
+    let message = match prompt(console, "Enter what the player is told when they can't go that way:") {
+        Some(message) => message,
+        None => return Ok(()),
+    };
+    let (index, mut operations) = match condition::position_of(&graph_file.conditions, &node_id, &edge) {
+        Some(index) => (index, vec![MapOperation::RemoveCondition { index }]),
+        None => (graph_file.conditions.len(), Vec::new()),
+    };
+    operations.push(MapOperation::InsertCondition { index, condition: EdgeCondition { node_id, edge, requirement, message } });
+    graph_file.change(MapOperation::Batch(operations))
+}
+
+fn remove_condition(console: &mut dyn Console, graph_file: &mut GraphFile) -> Result<(), GraphError> {
+    let node_id = current_node_id(&graph_file.graph)?;
+    let edges = graph_file.conditions.iter()
+        .filter(|condition| condition.node_id == node_id)
+        .map(|condition| condition.edge.clone())
+        .collect::<Vec<String>>();
+    if edges.is_empty() {
+        writeln!(console, "There aren't any conditions on directions from this location.");
+        return Ok(());
+    }
+    let edge = match prompt_with_options(console, "Enter the direction to remove the condition from:", edges.iter().map(|edge| edge.as_str()).collect()) {
+        Some(edge) => edge,
+        None => return Ok(()),
+    };
+    match condition::position_of(&graph_file.conditions, &node_id, &edge) {
+        Some(index) => graph_file.change(MapOperation::RemoveCondition { index }),
+        None => Ok(()),
+    }
+}
+
+fn undo(console: &mut dyn Console, graph_file: &mut GraphFile) -> Result<(), GraphError> {
+    if !graph_file.undo()? {
+        writeln!(console, "There isn't anything to undo.");
+    }
+    Ok(())
+}
+
+fn redo(console: &mut dyn Console, graph_file: &mut GraphFile) -> Result<(), GraphError> {
+    if !graph_file.redo()? {
+        writeln!(console, "There isn't anything to redo.");
+    }
+    Ok(())
+}
+
+/// Find the directions to the nearest location whose name or description contains `location`.
+/// Explains to the player why when there isn't anywhere to go.
+fn route_to_location(console: &mut dyn Console, graph: &LocationGraph, from_node_id: &str, location: &str) -> Option<Vec<String>> {
+    let distances = graph.distances_from(from_node_id);
+    let matching_node_ids = graph.nodes()
+        .filter(|node| node.borrow().element.matches(location))
+        .map(|node| node.borrow().id.clone())
+        .collect::<Vec<String>>();
+    if matching_node_ids.is_empty() {
+        writeln!(console, "There isn't a location like that.");
+        return None;
+    }
+    let destination_id = match matching_node_ids.iter()
+        .filter(|node_id| distances.contains_key(*node_id))
+        .min_by_key(|node_id| distances[*node_id]) {
+        Some(destination_id) => destination_id,
+        None => {
+            writeln!(console, "You can't get there from here.");
+            return None;
+        }
+    };
+    let route = graph.shortest_path(from_node_id, destination_id).unwrap_or_default();
+    if route.is_empty() {
+        writeln!(console, "You're already there.");
+        return None;
+    }
+    Some(route)
+}
+
+/// Walk to the nearest location whose name or description contains `location`, one step at a time. Returns
+/// whether the position changed.
+fn go_to_location(console: &mut dyn Console, graph: &mut LocationGraph, location: &str) -> Result<bool, GraphError> {
+    let route = match route_to_location(console, graph, &current_node_id(graph)?, location) {
+        Some(route) => route,
+        None => return Ok(false),
+    };
+    for direction in route {
+        let node = graph.traverse(direction.clone())?;
+        writeln!(console, "You go {}: {}", direction, node.borrow().element);
+    }
+    Ok(true)
+}
+
+/// The directions that lead out of the location for the provided node id.
+fn directions_from(graph: &LocationGraph, node_id: &str) -> Vec<String> {
+    graph.node(node_id).map(|node| node.borrow().edge_elements().collect()).unwrap_or_default()
+}
+
+fn move_to_location(console: &mut dyn Console, graph: &mut LocationGraph) {
+    let parser = Parser::new();
+    loop {
+        let directions = current_node_id(graph).map(|node_id| directions_from(graph, &node_id)).unwrap_or_default();
+        let input = match prompt(console, "Which way do you want to go? ") {
+            Some(input) => input,
+            None => return,
+        };
+        let command = match parser.parse(&input, &directions) {
+            Ok(command) => command,
+            Err(e) => {
+                writeln!(console, "{}", e);
+                continue;
+            }
+        };
         // TODO: If there aren't any valid directions, immediately exit.
         // TODO: If there's only one direction, just use it.
-        match graph.traverse(desired_direction) {
-            Some(_) => return true,
-            None => println!("That's not a valid direction. Give it another go...")
+        let result = match (command.verb, command.noun) {
+            (Verb::Quit, _) => return,
+            (Verb::GoTo, Some(location)) => go_to_location(console, graph, &location),
+            (Verb::Go, Some(direction)) => graph.traverse_matching(&direction, parser.direction_matcher()).map(|_| true),
+            _ => {
+                writeln!(console, "Only moving is possible while editing. Enter a direction, \"go to <location>\" or X.");
+                continue;
+            }
+        };
+        match result {
+            Ok(true) => return,
+            Ok(false) => (),
+            Err(e) => writeln!(console, "An error occurred: {}", e),
         }
     }
 }
 
-fn interactive_mode(graph: &mut StringGraph) {
+/// What happened when the player tried to follow a direction.
+enum Movement {
+    Moved,
+    /// The direction has a condition that the player doesn't meet, so they stayed where they were.
+    Blocked,
+    /// There isn't a direction like that from the player's location.
+    NoDirection,
+}
+
+/// Move the player along the direction from their current location, as long as they meet any
+/// condition on it. The author's message is printed when they don't.
+fn move_player(console: &mut dyn Console, graph_file: &GraphFile, game: &mut SaveGame, direction: &String) -> Result<Movement, GraphError> {
+    let graph = &graph_file.graph;
+    let target_node_id = match graph.node(&game.current_node_id)?.borrow().node_for_edge_element(direction) {
+        Some(target_node_id) => target_node_id,
+        None => return Ok(Movement::NoDirection),
+    };
+    if let Some(condition) = condition::condition_for(&graph_file.conditions, &game.current_node_id, direction) {
+        if !condition.requirement.is_met(game, &graph_file.items) {
+            writeln!(console, "{}", condition.message);
+            return Ok(Movement::Blocked);
+        }
+    }
+    graph.node(&target_node_id)?;
+    game.move_to(graph, target_node_id);
+    Ok(Movement::Moved)
+}
+
+/// Walk the player to the nearest location whose name or description contains `location`, stopping early if
+/// a condition blocks the way. Returns whether the turn is over.
+fn walk_player_to_location(console: &mut dyn Console, graph_file: &GraphFile, game: &mut SaveGame, location: &str) -> Result<bool, GraphError> {
+    let graph = &graph_file.graph;
+    let route = match route_to_location(console, graph, &game.current_node_id, location) {
+        Some(route) => route,
+        None => return Ok(false),
+    };
+    for direction in route {
+        match move_player(console, graph_file, game, &direction)? {
+            Movement::Moved => writeln!(console, "You go {}: {}", direction, graph.node(&game.current_node_id)?.borrow().element),
+            Movement::Blocked | Movement::NoDirection => break,
+        }
+    }
+    Ok(true)
+}
+
+fn save_game(console: &mut dyn Console, graph_file: &GraphFile, game: &mut SaveGame, slot: &str) {
+    if !save_game::is_valid_slot_name(slot) {
+        writeln!(console, "{}", save_game::INVALID_SLOT_NAME);
+        return;
+    }
+    match game.save(&graph_file.graph, &graph_file.items, slot) {
+        Ok(_) => writeln!(console, "Saved the game to the \"{}\" slot.", slot),
+        Err(e) => writeln!(console, "An error occurred while saving the game: {}", e),
+    }
+}
+
+fn load_game(console: &mut dyn Console, graph_file: &GraphFile, slot: &str) -> Option<SaveGame> {
+    if !save_game::is_valid_slot_name(slot) {
+        writeln!(console, "{}", save_game::INVALID_SLOT_NAME);
+        return None;
+    }
+    let game = match SaveGame::load(&graph_file.file_name, slot) {
+        Ok(game) => game,
+        Err(e) => {
+            writeln!(console, "An error occurred while loading the game: {}", e);
+            return None;
+        }
+    };
+    if graph_file.graph.node(&game.current_node_id).is_err() {
+        writeln!(console, "The location that the game was saved at isn't in the map anymore.");
+        return None;
+    }
+    if game.map_changed(&graph_file.graph, &graph_file.items) {
+        writeln!(console, "The map has been edited since this game was saved.");
+    }
+    writeln!(console, "Loaded the game from the \"{}\" slot.", slot);
+    Some(game)
+}
+
+fn print_save_slots(console: &mut dyn Console, map_file: &str) {
+    let slots = save_game::slots(map_file);
+    if slots.is_empty() {
+        writeln!(console, "There aren't any saved games.");
+    } else {
+        writeln!(console, "Saved games: {}", slots.join(", "));
+    }
+}
+
+fn start_game(console: &mut dyn Console, graph_file: &GraphFile) -> Result<Option<SaveGame>, GraphError> {
+    if !save_game::slots(&graph_file.file_name).is_empty() {
+        match prompt_with_options(console, "Start a new game (N) or load a saved game (L)?", vec!["n", "N", "l", "L"]).as_deref() {
+            Some("l" | "L") => {
+                print_save_slots(console, &graph_file.file_name);
+                return Ok(prompt(console, "Enter the name of the save slot:").and_then(|slot| load_game(console, graph_file, &slot)));
+            }
+            Some(_) => (),
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(SaveGame::new(&graph_file.file_name, &graph_file.graph, &graph_file.items, current_node_id(&graph_file.graph)?)))
+}
+
+/// Every item that's at the provided location in the game.
+fn items_at<'a>(items: &'a [Item], game: &'a SaveGame, location: &'a ItemLocation) -> impl Iterator<Item=&'a Item> + 'a {
+    items.iter().filter(move |item| game.item_location(item) == location)
+}
+
+fn find_item<'a>(items: &'a [Item], game: &SaveGame, location: &ItemLocation, name: &str) -> Option<&'a Item> {
+    items.iter().find(|item| game.item_location(item) == location && item.is_called(name))
+}
+
+fn print_inventory(console: &mut dyn Console, items: &[Item], game: &SaveGame) {
+    let item_names = items_at(items, game, &ItemLocation::Inventory)
+        .map(|item| item.name.as_str())
+        .collect::<Vec<&str>>();
+    if item_names.is_empty() {
+        writeln!(console, "You aren't carrying anything.");
+    } else {
+        writeln!(console, "You're carrying: {}", item_names.join(", "));
+    }
+}
+
+fn take_item(console: &mut dyn Console, items: &[Item], game: &mut SaveGame, name: &str) {
+    match find_item(items, game, &ItemLocation::Node(game.current_node_id.clone()), name) {
+        Some(item) => {
+            game.move_item(item, ItemLocation::Inventory);
+            writeln!(console, "You take the {}.", item.name);
+        }
+        None => writeln!(console, "There isn't a {} here.", name),
+    }
+}
+
+fn drop_item(console: &mut dyn Console, items: &[Item], game: &mut SaveGame, name: &str) {
+    match find_item(items, game, &ItemLocation::Inventory, name) {
+        Some(item) => {
+            game.move_item(item, ItemLocation::Node(game.current_node_id.clone()));
+            writeln!(console, "You drop the {}.", item.name);
+        }
+        None => writeln!(console, "You aren't carrying a {}.", name),
+    }
+}
+
+fn examine_item(console: &mut dyn Console, items: &[Item], game: &SaveGame, name: &str) {
+    let item = find_item(items, game, &ItemLocation::Inventory, name)
+        .or_else(|| find_item(items, game, &ItemLocation::Node(game.current_node_id.clone()), name));
+    match item {
+        Some(item) => writeln!(console, "{}", item.description),
+        None => writeln!(console, "You can't see a {} anywhere.", name),
+    }
+}
+
+/// Draw the locations around the one for the node id, going as many steps away as `distance` says,
+/// or the default when it's blank.
+fn print_minimap(console: &mut dyn Console, graph: &LocationGraph, node_id: &str, distance: &str) {
+    let distance = match distance {
+        "" => DEFAULT_MAP_DISTANCE,
+        _ => match distance.parse::<usize>() {
+            Ok(distance) => distance,
+            Err(_) => {
+                writeln!(console, "The map can only go a number of steps away, like \"map 3\".");
+                return;
+            }
+        },
+    };
+    match graph.minimap(node_id, distance) {
+        Ok(minimap) => writeln!(console, "{}", minimap),
+        Err(e) => writeln!(console, "An error occurred: {}", e),
+    }
+}
+
+fn print_help(console: &mut dyn Console, parser: &Parser) {
+    writeln!(console, "{:<40}Words that work:", "You can:");
+    for verb in Verb::ALL {
+        let phrases = parser.phrases(verb);
+        if phrases.is_empty() {
+            continue;
+        }
+        writeln!(console, "    {:<36}{}", verb.usage(), phrases.join(", "));
+    }
+}
+
+/// Take the player's next turn. Returns whether they want to keep playing.
+fn play_turn(console: &mut dyn Console, graph_file: &GraphFile, parser: &Parser, game: &mut SaveGame) -> bool {
     loop {
-        print_current_location(graph);
-        if !move_to_location(graph) {
+        let directions = directions_from(&graph_file.graph, &game.current_node_id);
+        // Running out of input ends the game the same as quitting.
+        let input = match prompt(console, "What do you want to do?") {
+            Some(input) => input,
+            None => return false,
+        };
+        let command = match parser.parse(&input, &directions) {
+            Ok(command) => command,
+            Err(e) => {
+                writeln!(console, "{}", e);
+                continue;
+            }
+        };
+        let noun = command.noun.unwrap_or_default();
+        match command.verb {
+            Verb::Quit => return false,
+            Verb::Look => return true,
+            Verb::Help => print_help(console, parser),
+            Verb::Go => match move_player(console, graph_file, game, &noun) {
+                Ok(Movement::Moved) | Ok(Movement::Blocked) => return true,
+                Ok(Movement::NoDirection) => writeln!(console, "You can't go \"{}\" from here.", noun),
+                Err(e) => writeln!(console, "An error occurred: {}", e),
+            },
+            Verb::GoTo => match walk_player_to_location(console, graph_file, game, &noun) {
+                Ok(true) => return true,
+                Ok(false) => (),
+                Err(e) => writeln!(console, "An error occurred: {}", e),
+            },
+            Verb::Inventory => print_inventory(console, &graph_file.items, game),
+            Verb::Map => print_minimap(console, &graph_file.graph, &game.current_node_id, &noun),
+            Verb::Take => take_item(console, &graph_file.items, game, &noun),
+            Verb::Drop => drop_item(console, &graph_file.items, game, &noun),
+            Verb::Examine => examine_item(console, &graph_file.items, game, &noun),
+            Verb::Saves => print_save_slots(console, &graph_file.file_name),
+            Verb::Save => save_game(console, graph_file, game, &noun),
+            Verb::Load => {
+                if let Some(loaded_game) = load_game(console, graph_file, &noun) {
+                    *game = loaded_game;
+                    return true;
+                }
+            }
+        }
+    }
+}
+
+/// Play the map from the current location. Progress is kept in a SaveGame, so the map itself is
+/// never changed by playing.
+fn interactive_mode(console: &mut dyn Console, graph_file: &GraphFile) {
+    match start_game(console, graph_file) {
+        Ok(Some(game)) => play(console, graph_file, game),
+        Ok(None) => (),
+        Err(e) => writeln!(console, "An error occurred: {}", e),
+    }
+}
+
+/// Play a new game from the start of the map. Transcripts are recorded and replayed this way so that
+/// they don't depend on saved games or on where the map was last being edited.
+fn play_from_start(console: &mut dyn Console, graph_file: &GraphFile) {
+    let game = SaveGame::new(&graph_file.file_name, &graph_file.graph, &graph_file.items, graph_file.graph.root_node_id().to_string());
+    play(console, graph_file, game);
+}
+
+/// Play the game until the player quits or runs out of input.
+fn play(console: &mut dyn Console, graph_file: &GraphFile, mut game: SaveGame) {
+    let parser = Parser::new();
+    writeln!(console, "Enter a direction to move, or \"help\" to see everything you can do.");
+    // A new game starts with the player seeing the first location for the first time.
+    let mut first_visit = game.turns == 0;
+    loop {
+        let result = graph_file.graph.node(&game.current_node_id).map(|node| {
+            print_location(console, &node.borrow(), first_visit, items_at(&graph_file.items, &game, &ItemLocation::Node(game.current_node_id.clone())))
+        });
+        report(console, result);
+        let visited = game.visited.clone();
+        if !play_turn(console, graph_file, &parser, &mut game) {
             break;
         }
+        first_visit = !visited.contains(&game.current_node_id);
+    }
+}
+
+/// Ask for a new value, which is None when the current one should be kept. Returns None when there
+/// isn't any more input.
+fn prompt_for_change(console: &mut dyn Console, prompt_text: &str, current: &str) -> Option<Option<String>> {
+    let input = prompt(console, &format!("{} (leave blank to keep \"{}\"):", prompt_text, current))?;
+    Some((!input.is_empty()).then_some(input))
+}
+
+fn prompt_to_update(console: &mut dyn Console, prompt_text: &str, value: &mut Option<String>) -> Option<()> {
+    if let Some(input) = prompt_for_change(console, prompt_text, value.as_deref().unwrap_or(""))? {
+        *value = Some(input);
+    }
+    Some(())
+}
+
+fn prompt_to_update_text(console: &mut dyn Console, prompt_text: &str, value: &mut String) -> Option<()> {
+    if let Some(input) = prompt_for_change(console, prompt_text, value)? {
+        *value = input;
+    }
+    Some(())
+}
+
+/// Replace a list with a comma separated one that's entered, or clear it when a single dash is.
+fn prompt_to_update_list(console: &mut dyn Console, prompt_text: &str, list: &mut BTreeSet<String>) -> Option<()> {
+    let current = list.iter().map(String::as_str).collect::<Vec<&str>>().join(", ");
+    match prompt_for_change(console, prompt_text, &current)? {
+        Some(input) if input == "-" => list.clear(),
+        Some(input) => *list = input.split(',')
+            .map(|entry| entry.trim().to_string())
+            .filter(|entry| !entry.is_empty())
+            .collect(),
+        None => (),
+    }
+    Some(())
+}
+
+fn update_map_details(console: &mut dyn Console, graph_file: &mut GraphFile) -> Result<(), GraphError> {
+    let mut metadata = graph_file.graph.metadata().clone();
+    // The details are left as they were when the input runs out part way through.
+    let entered = prompt_to_update(console, "Enter the title of your map", &mut metadata.title)
+        .and_then(|_| prompt_to_update(console, "Enter the author of your map", &mut metadata.author));
+    let current = graph_file.graph.metadata();
+    if entered.is_none() || (&metadata.title, &metadata.author) == (&current.title, &current.author) {
+        return Ok(());
+    }
+    graph_file.apply(Operation::SetMetadata { metadata })
+}
+
+fn print_locations(console: &mut dyn Console, heading: &str, graph: &LocationGraph, node_ids: &[String]) {
+    writeln!(console, "\n{}:", heading);
+    if node_ids.is_empty() {
+        writeln!(console, "    None");
+    }
+    for node_id in node_ids {
+        match graph.node(node_id) {
+            Ok(node) => writeln!(console, "    {}", node.borrow().element),
+            Err(e) => writeln!(console, "    An error occurred: {}", e),
+        }
     }
 }
 
-fn save(graph_file: &GraphFile) {
-    match serde_json::to_string(&graph_file.graph) {
-        Ok(data) => {
-            match fs::write(&graph_file.file_name, data.as_bytes()) {
-                Ok(_) => (),
-                Err(e) => println!("An error occurred: {:#?}", e)
+/// Write the map in the format that goes with the extension of the file name. DOT highlights where
+/// the map starts and the current location.
+fn export_map(console: &mut dyn Console, graph_file: &GraphFile) {
+    let file_name = match prompt(console, "Enter the file name, ending in .dot, .mmd, .graphml, .twee or .html:") {
+        Some(file_name) => file_name,
+        None => return,
+    };
+    let format = ExportFormat::for_file(&file_name);
+    let exporter: Box<dyn Exporter<Location, String>> = match format {
+        Some(ExportFormat::Dot) => match prompt_for_dot_options(console) {
+            Some(options) => Box::new(options),
+            None => return,
+        },
+        Some(ExportFormat::Mermaid) => Box::new(Mermaid),
+        Some(ExportFormat::GraphMl) => Box::new(GraphMl),
+        Some(ExportFormat::Twee) => Box::new(Twee),
+        Some(ExportFormat::Html) => Box::new(Html),
+        None => {
+            writeln!(console, "Maps can only be exported to .dot, .gv, .mmd, .graphml, .twee or .html files.");
+            return;
+        }
+    };
+    match fs::write(&file_name, exporter.export(&graph_file.graph)) {
+        Ok(()) => writeln!(console, "The map has been written to {}.", file_name),
+        Err(e) => {
+            writeln!(console, "An error occurred while writing {}: {}", file_name, e);
+            return;
+        }
+    }
+    for warning in format.map(|format| export_warnings(graph_file, format)).unwrap_or_default() {
+        writeln!(console, "Warning: {}", warning);
+    }
+}
+
+/// Ask how a DOT file should be drawn. Returns None when the input runs out.
+fn prompt_for_dot_options(console: &mut dyn Console) -> Option<DotOptions> {
+    Some(DotOptions {
+        highlight_root: true,
+        highlight_current: true,
+        collapse_bidirectional: matches!(prompt_with_options(console, "Draw directions that lead both ways as a single line (Y/N)?", vec!["y", "Y", "n", "N"])?.as_str(), "y" | "Y"),
+        cluster_by_region: matches!(prompt_with_options(console, "Group locations by their region tag (Y/N)?", vec!["y", "Y", "n", "N"])?.as_str(), "y" | "Y"),
+    })
+}
+
+/// Anything in the map that the provided format can't hold, which is left out or changed when the
+/// map is exported.
+fn export_warnings(graph_file: &GraphFile, format: ExportFormat) -> Vec<String> {
+    match format {
+        ExportFormat::Twee => graph_file.graph.twee_issues().iter().map(ToString::to_string).collect(),
+        ExportFormat::Html => {
+            let mut warnings = Vec::new();
+            if !graph_file.items.is_empty() {
+                warnings.push("The page leaves out the map's items, so they can't be picked up when it's played.".to_string());
             }
+            if !graph_file.conditions.is_empty() {
+                warnings.push("The page leaves out the map's conditions, so every direction can be taken when it's played.".to_string());
+            }
+            warnings
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn print_map_report(console: &mut dyn Console, graph: &LocationGraph) {
+    print_locations(console, "Locations that can't be reached from the start", graph, &graph.unreachable_nodes());
+    print_locations(console, "Dead ends with no way out", graph, &graph.dead_ends());
+    print_locations(console, "One-way traps with no way back to the start", graph, &graph.one_way_traps());
+    for (idx, component) in graph.strongly_connected_components().iter().enumerate() {
+        print_locations(console, &format!("Region {} (every location can reach the others)", idx + 1), graph, component);
+    }
+}
+
+fn print_validation_report(console: &mut dyn Console, report: &ValidationReport) {
+    if report.is_empty() {
+        writeln!(console, "No problems found.");
+        return;
+    }
+    for issue in report.issues() {
+        let severity = if issue.is_error() { "Error" } else { "Warning" };
+        writeln!(console, "{}: {}", severity, issue);
+    }
+}
+
+/// A Graph that was read from a map file along with what was found while reading it.
+struct LoadedGraph {
+    graph: LocationGraph,
+    items: Vec<Item>,
+    conditions: Vec<EdgeCondition>,
+    report: ValidationReport,
+    /// The format version of the file, if it was older than the current one.
+    upgraded_from: Option<u32>,
+}
+
+/// Add a warning to the report for every item that's placed in a location that isn't in the Graph.
+fn check_items(graph: &LocationGraph, items: &[Item], report: &mut ValidationReport) {
+    for item in items {
+        if let ItemLocation::Node(node_id) = &item.location {
+            if graph.node(node_id).is_err() {
+                report.push(ValidationIssue::MisplacedItem { item: item.name.clone(), node_id: node_id.clone() });
+            }
+        }
+    }
+}
+
+/// Add a warning to the report for every condition that's on a direction that isn't in the Graph or
+/// that can never be met.
+fn check_conditions(graph: &LocationGraph, items: &[Item], conditions: &[EdgeCondition], report: &mut ValidationReport) {
+    for condition in conditions {
+        let (node_id, edge) = (condition.node_id.clone(), condition.edge.clone());
+        if condition.is_on_missing_edge(graph) {
+            report.push(ValidationIssue::ConditionOnMissingEdge { node_id, edge });
+        } else if !condition.requirement.can_be_met(graph, items) {
+            report.push(ValidationIssue::UnmeetableCondition { node_id, edge });
+        }
+    }
+}
+
+fn parse_graph(graph_data: &str, mode: ValidationMode) -> Result<LoadedGraph, String> {
+    let value = serde_json::from_str(graph_data).map_err(|e| e.to_string())?;
+    let migrated = migration::migrate(value).map_err(|e| e.to_string())?;
+    let (graph, mut report) = LocationGraph::deserialize_with_mode(&migrated.value, mode).map_err(|e| e.to_string())?;
+    let map_data = MapData::deserialize(&migrated.value).map_err(|e| e.to_string())?;
+    check_items(&graph, &map_data.items, &mut report);
+    check_conditions(&graph, &map_data.items, &map_data.conditions, &mut report);
+    Ok(LoadedGraph { graph, items: map_data.items, conditions: map_data.conditions, report, upgraded_from: migrated.was_upgraded().then_some(migrated.from_version) })
+}
+
+fn read_graph(path: &Path, mode: ValidationMode) -> Result<LoadedGraph, String> {
+    let graph_data = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    parse_graph(graph_data.as_str(), mode)
+}
+
+fn recover_from_backup(console: &mut dyn Console, file_name: &str) -> Option<LoadedGraph> {
+    for backup in storage::backups(Path::new(file_name)) {
+        if let Ok(loaded) = read_graph(&backup, ValidationMode::Strict) {
+            let prompt_text = format!("The newest valid backup is {}. Recover from it (Y/N)?", backup.display());
+            return match prompt_with_options(console, &prompt_text, vec!["y", "Y", "n", "N"])?.as_str() {
+                "y" | "Y" => {
+                    writeln!(console, "Recovered from the backup. The next save will replace {}.", file_name);
+                    Some(loaded)
+                }
+                _ => None,
+            };
+        }
+    }
+    writeln!(console, "There aren't any valid backups to recover from.");
+    None
+}
+
+fn load(console: &mut dyn Console, file_name: &str) -> Option<GraphFile> {
+    let (loaded, repaired) = match read_graph(Path::new(file_name), ValidationMode::Strict) {
+        Ok(loaded) => (loaded, false),
+        Err(e) => {
+            writeln!(console, "Issue loading game data: {}", e);
+            let loaded = match prompt_with_options(console, "Load whatever can be loaded (L), recover from a backup (B) or give up (X)?", vec!["l", "L", "b", "B", "x", "X"])?.as_str() {
+                "l" | "L" => match read_graph(Path::new(file_name), ValidationMode::Lenient) {
+                    Ok(loaded) => loaded,
+                    Err(e) => {
+                        writeln!(console, "Issue loading game data: {}", e);
+                        recover_from_backup(console, file_name)?
+                    }
+                },
+                "b" | "B" => recover_from_backup(console, file_name)?,
+                _ => return None,
+            };
+            (loaded, true)
+        }
+    };
+    if !loaded.report.is_empty() {
+        writeln!(console, "The map was loaded with the following problems:");
+        print_validation_report(console, &loaded.report);
+    }
+    let upgraded_from = loaded.upgraded_from;
+    let mut graph_file = GraphFile::from_loaded(file_name, loaded);
+    if repaired {
+        // The file doesn't hold what was loaded, so the next save has to replace it.
+        graph_file.saved = None;
+    }
+    if let Some(from_version) = upgraded_from {
+        writeln!(console, "The map was saved in format version {}, which is older than the current version {}.",
+                 from_version, FORMAT_VERSION);
+        if let Some("y" | "Y") = prompt_with_options(console, "Rewrite the file in the newest format (Y/N)?", vec!["y", "Y", "n", "N"]).as_deref() {
+            graph_file.saved = None;
+            save(console, &mut graph_file);
+        }
+    }
+    Some(graph_file)
+}
+
+/// Write the map to its file, keeping backups of the previous versions. Nothing is written when
+/// the map hasn't changed since it was last saved or loaded, so that the backups aren't rotated away
+/// by saves that wouldn't change anything.
+fn write_map(graph_file: &mut GraphFile) -> Result<(), String> {
+    if graph_file.saved.as_deref() == Some(map_data(graph_file)?.as_str()) {
+        return Ok(());
+    }
+    graph_file.graph.metadata_mut().touch();
+    let data = map_data(graph_file)?;
+    storage::write_atomic(Path::new(&graph_file.file_name), data.as_bytes(), storage::BACKUP_COUNT).map_err(|e| e.to_string())?;
+    graph_file.saved = Some(data);
+    Ok(())
+}
+
+/// Everything that's written to the map's file.
+fn map_data(graph_file: &GraphFile) -> Result<String, String> {
+    let map = MapFile { graph: &graph_file.graph, items: &graph_file.items, conditions: &graph_file.conditions };
+    serde_json::to_string(&map).map_err(|e| e.to_string())
+}
+
+/// Read a Twine story from a Twee file into a new map, which is saved straight away.
+fn import_story(console: &mut dyn Console) -> Option<GraphFile> {
+    let twee_file = prompt(console, "Enter the file name of the Twee story:")?;
+    let graph = match fs::read_to_string(&twee_file).map_err(|e| e.to_string())
+        .and_then(|twee| LocationGraph::from_twee(&twee).map_err(|e| e.to_string())) {
+        Ok((graph, issues)) => {
+            for issue in issues {
+                writeln!(console, "Warning: {}", issue);
+            }
+            graph
+        }
+        Err(e) => {
+            writeln!(console, "Issue importing {}: {}", twee_file, e);
+            return None;
         }
-        Err(e) => println!("An error occurred while writing: {:#?}", e),
+    };
+    let file_name = prompt(console, "Enter the file name for your graph:")?;
+    let mut graph_file = GraphFile::new(&file_name, graph);
+    save(console, &mut graph_file);
+    Some(graph_file)
+}
+
+fn save(console: &mut dyn Console, graph_file: &mut GraphFile) {
+    if let Err(e) = write_map(graph_file) {
+        writeln!(console, "An error occurred while saving: {}", e);
     }
 }
 
+/// The items and conditions in a map file, which are stored alongside the Graph.
+#[derive(Deserialize)]
+struct MapData {
+    #[serde(default)]
+    items: Vec<Item>,
+    #[serde(default)]
+    conditions: Vec<EdgeCondition>,
+}
+
+/// Everything that's written to a map file.
+#[derive(Serialize)]
+struct MapFile<'a> {
+    #[serde(flatten)]
+    graph: &'a LocationGraph,
+    items: &'a [Item],
+    conditions: &'a [EdgeCondition],
+}
+
 struct GraphFile {
-    graph: StringGraph,
+    graph: LocationGraph,
+    items: Vec<Item>,
+    conditions: Vec<EdgeCondition>,
+    history: History<MapOperation>,
     file_name: String,
+    /// What the file held when it was last saved or loaded, if it's known to match the map.
+    saved: Option<String>,
 }
 
-fn main() {
-    // TODO: Allow reading/writing specific files by adding an initial menu before the one below.
+/// The parts of a map that a MapOperation changes.
+struct MapParts<'a> {
+    graph: &'a mut LocationGraph,
+    items: &'a mut Vec<Item>,
+    conditions: &'a mut Vec<EdgeCondition>,
+}
 
-    loop {
-        let mut graph_file = match main_menu().as_str() {
-            "1" => {
-                GraphFile {
-                    file_name: prompt("Enter the file name for your graph:"),
-                    graph: StringGraph::new(prompt("Enter the description of your first node:")),
+/// A reversible change to a map. Changes to the Graph are Operations, while the items and conditions,
+/// which aren't part of the Graph, are changed one at a time by their position in the map.
+#[derive(Clone)]
+enum MapOperation {
+    Graph(Operation<Location, String>),
+    /// Put an item at `index` among the items.
+    InsertItem { index: usize, item: Item },
+    /// Remove the item at `index`, if there is one.
+    RemoveItem { index: usize },
+    /// Put a condition at `index` among the conditions.
+    InsertCondition { index: usize, condition: EdgeCondition },
+    /// Remove the condition at `index`, if there is one.
+    RemoveCondition { index: usize },
+    /// Remove every condition that's left over from something that's been removed from the map.
+    /// Which ones are stale is worked out when it's applied, so it can follow the changes that made
+    /// them stale in the same Batch.
+    RemoveStaleConditions,
+    /// Several MapOperations that are applied in order and undone together.
+    Batch(Vec<MapOperation>),
+}
+
+impl<'a> Undoable<MapParts<'a>> for MapOperation {
+    fn apply(self, map: &mut MapParts<'a>) -> Result<Self, GraphError> {
+        match self {
+            MapOperation::Graph(operation) => Ok(MapOperation::Graph(operation.apply(map.graph)?)),
+            MapOperation::InsertItem { index, item } => {
+                map.items.insert(index.min(map.items.len()), item);
+                Ok(MapOperation::RemoveItem { index })
+            }
+            MapOperation::RemoveItem { index } if index < map.items.len() =>
+                Ok(MapOperation::InsertItem { index, item: map.items.remove(index) }),
+            MapOperation::InsertCondition { index, condition } => {
+                map.conditions.insert(index.min(map.conditions.len()), condition);
+                Ok(MapOperation::RemoveCondition { index })
+            }
+            MapOperation::RemoveCondition { index } if index < map.conditions.len() =>
+                Ok(MapOperation::InsertCondition { index, condition: map.conditions.remove(index) }),
+            // Removing from the end first keeps the positions of the rest the same.
+            MapOperation::RemoveStaleConditions => MapOperation::Batch(map.conditions.iter()
+                .enumerate()
+                .filter(|(_, condition)| condition.is_stale(map.graph, map.items))
+                .map(|(index, _)| MapOperation::RemoveCondition { index })
+                .rev()
+                .collect()).apply(map),
+            MapOperation::Batch(operations) => {
+                let mut inverse = Vec::with_capacity(operations.len());
+                for operation in operations {
+                    match operation.apply(map) {
+                        Ok(inverse_operation) => inverse.push(inverse_operation),
+                        Err(e) => {
+                            // Roll back whatever was already applied so the Batch is all or nothing.
+                            while let Some(inverse_operation) = inverse.pop() {
+                                let _ = inverse_operation.apply(map);
+                            }
+                            return Err(e);
+                        }
+                    }
                 }
+                inverse.reverse();
+                Ok(MapOperation::Batch(inverse))
             }
-            "2" => {
-                let file_name = prompt("Enter the file name for your graph:");
-                let graph_data = fs::read_to_string(&file_name).expect("Issue loading game data.");
-                GraphFile {
-                    graph: serde_json::from_str(graph_data.as_str()).expect("Issue deserializing game data."),
-                    file_name: file_name,
+            // There isn't anything at the position to remove, so nothing changes.
+            MapOperation::RemoveItem { .. } | MapOperation::RemoveCondition { .. } => Ok(MapOperation::Batch(Vec::new())),
+        }
+    }
+}
+
+impl GraphFile {
+    /// A map that only has the provided Graph in it.
+    fn new(file_name: &str, graph: LocationGraph) -> Self {
+        GraphFile {
+            graph,
+            items: Vec::new(),
+            conditions: Vec::new(),
+            history: History::new(),
+            file_name: file_name.to_string(),
+            saved: None,
+        }
+    }
+
+    fn from_loaded(file_name: &str, loaded: LoadedGraph) -> Self {
+        let mut graph_file = GraphFile {
+            items: loaded.items,
+            conditions: loaded.conditions,
+            ..GraphFile::new(file_name, loaded.graph)
+        };
+        graph_file.saved = map_data(&graph_file).ok();
+        graph_file
+    }
+
+    /// Apply the Operation to the Graph so that it can be undone.
+    fn apply(&mut self, operation: Operation<Location, String>) -> Result<(), GraphError> {
+        self.change(MapOperation::Graph(operation))
+    }
+
+    /// Make a change to the map that can be undone in a single step.
+    fn change(&mut self, operation: MapOperation) -> Result<(), GraphError> {
+        let mut map = MapParts { graph: &mut self.graph, items: &mut self.items, conditions: &mut self.conditions };
+        self.history.apply(&mut map, operation)
+    }
+
+    /// Reverse the most recent change. Returns whether there was anything to undo.
+    fn undo(&mut self) -> Result<bool, GraphError> {
+        let mut map = MapParts { graph: &mut self.graph, items: &mut self.items, conditions: &mut self.conditions };
+        self.history.undo(&mut map)
+    }
+
+    /// Make the most recently undone change again. Returns whether there was anything to redo.
+    fn redo(&mut self) -> Result<bool, GraphError> {
+        let mut map = MapParts { graph: &mut self.graph, items: &mut self.items, conditions: &mut self.conditions };
+        self.history.redo(&mut map)
+    }
+}
+
+/// Show the menus until the user exits or there isn't any more input.
+fn run_menus(console: &mut dyn Console) {
+    loop {
+        let mut graph_file = match main_menu(console).as_deref() {
+            Some("1") => {
+                let new_map = prompt(console, "Enter the file name for your graph:").and_then(|file_name| {
+                    prompt_for_location(console).map(|location| GraphFile::new(&file_name, LocationGraph::new(location)))
+                });
+                match new_map {
+                    Some(graph_file) => graph_file,
+                    None => continue,
                 }
             }
-            "X" | "x" => break,
+            Some("2") => match prompt(console, "Enter the file name for your graph:").and_then(|file_name| load(console, &file_name)) {
+                Some(graph_file) => graph_file,
+                None => continue,
+            },
+            Some("3") => match import_story(console) {
+                Some(graph_file) => graph_file,
+                None => continue,
+            },
+            Some("X" | "x") | None => break,
             _ => continue
         };
         loop {
-            match location_edit_menu(&graph_file.graph).as_str() {
-                "1" => {
-                    update_location_description(&mut graph_file.graph);
-                    save(&graph_file)
+            // Every option that can change the map saves it afterwards, if it did.
+            let result = match location_edit_menu(console, &graph_file).as_deref() {
+                Some("1") => update_location(console, &mut graph_file),
+                Some("2") => connect_location(console, &mut graph_file),
+                Some("3") => {
+                    move_to_location(console, &mut graph_file.graph);
+                    continue;
                 }
-                "2" => {
-                    connect_location(&mut graph_file.graph);
-                    save(&graph_file)
+                Some("4") => {
+                    interactive_mode(console, &graph_file);
+                    continue;
                 }
-                "3" => {
-                    move_to_location(&mut graph_file.graph);
-                    ()
+                Some("5") => {
+                    graph_file.graph.reset();
+                    continue;
                 }
-                "4" => interactive_mode(&mut graph_file.graph),
-                "5" => graph_file.graph.reset(),
-                "X" | "x" => break,
-                _ => ()
-            }
+                Some("6") => delete_location(console, &mut graph_file),
+                Some("7") => remove_direction(console, &mut graph_file),
+                Some("8") => retarget_direction(console, &mut graph_file),
+                Some("9") => {
+                    let mut report = graph_file.graph.validate();
+                    check_items(&graph_file.graph, &graph_file.items, &mut report);
+                    check_conditions(&graph_file.graph, &graph_file.items, &graph_file.conditions, &mut report);
+                    print_validation_report(console, &report);
+                    continue;
+                }
+                Some("10") => update_map_details(console, &mut graph_file),
+                Some("11") => {
+                    print_map_report(console, &graph_file.graph);
+                    continue;
+                }
+                Some("12") => undo(console, &mut graph_file),
+                Some("13") => redo(console, &mut graph_file),
+                Some("14") => place_item(console, &mut graph_file),
+                Some("15") => remove_item(console, &mut graph_file),
+                Some("16") => edit_condition(console, &mut graph_file),
+                Some("17") => remove_condition(console, &mut graph_file),
+                Some("18") => {
+                    export_map(console, &graph_file);
+                    continue;
+                }
+                Some("19") => {
+                    let distance = match prompt(console, &format!("How many steps away should the map go (leave blank for {})?", DEFAULT_MAP_DISTANCE)) {
+                        Some(distance) => distance,
+                        None => break,
+                    };
+                    let node_id = graph_file.graph.current_node().map(|node| node.borrow().id.clone());
+                    match node_id {
+                        Ok(node_id) => print_minimap(console, &graph_file.graph, &node_id, &distance),
+                        Err(e) => writeln!(console, "An error occurred: {}", e),
+                    }
+                    continue;
+                }
+                Some("20") => lay_out_map(console, &mut graph_file),
+                Some("X" | "x") | None => break,
+                _ => continue,
+            };
+            report(console, result);
+            save(console, &mut graph_file);
         }
     }
 }
+
+fn main() {
+    // Any arguments are a command to run without the menus.
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    if !args.is_empty() {
+        process::exit(cli::run(&args));
+    }
+    run_menus(&mut StdConsole);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::ScriptedConsole;
+    use crate::graph::Position;
+
+    /// A hall with a locked door to the north and a key lying in it. The map's file is in a
+    /// directory that doesn't exist, so there aren't any saved games for it.
+    fn house() -> GraphFile {
+        let mut graph = LocationGraph::new(Location::new("Hall".to_string(), "A dusty hall.".to_string()));
+        let hall_id = graph.root_node_id().to_string();
+        let study = Node::new(Location::new("Study".to_string(), "A quiet study.".to_string()));
+        let study_id = study.id.clone();
+        graph.insert_node(Rc::new(RefCell::new(study))).unwrap();
+        graph.insert_edge_both_ways(&hall_id, "north".to_string(), study_id).unwrap();
+        let mut graph_file = GraphFile::new("missing-directory/house.json", graph);
+        let key = Item::new("key".to_string(), "A brass key.".to_string(), ItemLocation::Node(hall_id.clone()));
+        graph_file.conditions.push(EdgeCondition {
+            node_id: hall_id,
+            edge: "north".to_string(),
+            requirement: Requirement::HoldsItem { item_id: key.id.clone() },
+            message: "The door is locked.".to_string(),
+        });
+        graph_file.items.push(key);
+        graph_file
+    }
+
+    fn current_location(graph: &LocationGraph) -> String {
+        graph.current_node().unwrap().borrow().element.name.clone()
+    }
+
+    #[test]
+    fn exporting_warns_about_what_the_format_leaves_out() {
+        let graph_file = house();
+        assert_eq!(export_warnings(&graph_file, ExportFormat::Html), vec![
+            "The page leaves out the map's items, so they can't be picked up when it's played.".to_string(),
+            "The page leaves out the map's conditions, so every direction can be taken when it's played.".to_string(),
+        ]);
+        assert!(export_warnings(&graph_file, ExportFormat::Dot).is_empty());
+        assert!(export_warnings(&graph_file, ExportFormat::Twee).is_empty());
+    }
+
+    #[test]
+    fn connecting_a_new_location_adds_the_way_back() {
+        let mut graph_file = house();
+        let mut console = ScriptedConsole::new(&["e", "n", "Garden", "An overgrown garden.", "y"]);
+        connect_location(&mut console, &mut graph_file).unwrap();
+        assert_eq!(current_location(&graph_file.graph), "Garden");
+        graph_file.graph.traverse("west".to_string()).unwrap();
+        assert_eq!(current_location(&graph_file.graph), "Hall");
+        assert!(console.output().contains("Use \"west\" as the way back (Y)"), "{}", console.output());
+    }
+
+    #[test]
+    fn connecting_an_existing_location_can_use_a_different_way_back() {
+        let mut graph_file = house();
+        let mut console = ScriptedConsole::new(&["through the mirror", "x", "e", "2", "y", "step out"]);
+        connect_location(&mut console, &mut graph_file).unwrap();
+        assert_eq!(current_location(&graph_file.graph), "Study");
+        graph_file.graph.traverse("step out".to_string()).unwrap();
+        assert_eq!(current_location(&graph_file.graph), "Hall");
+        assert!(console.output().contains("Invalid input. Give it another go..."), "{}", console.output());
+    }
+
+    #[test]
+    fn select_node_asks_again_until_a_location_is_picked() {
+        let graph_file = house();
+        let mut console = ScriptedConsole::new(&["study", "3", "2"]);
+        let node_id = select_node(&mut console, &graph_file.graph).unwrap();
+        assert_eq!(graph_file.graph.node(&node_id).unwrap().borrow().element.name, "Study");
+        assert_eq!(console.output().matches("Invalid input. Give it another go...").count(), 2, "{}", console.output());
+    }
+
+    #[test]
+    fn running_out_of_input_part_way_through_an_edit_changes_nothing() {
+        let mut graph_file = house();
+        connect_location(&mut ScriptedConsole::ending(&["east", "n", "Garden"]), &mut graph_file).unwrap();
+        update_location(&mut ScriptedConsole::ending(&["Foyer"]), &mut graph_file).unwrap();
+        place_item(&mut ScriptedConsole::ending(&["lamp"]), &mut graph_file).unwrap();
+        assert_eq!(graph_file.graph.nodes().count(), 2);
+        assert_eq!(current_location(&graph_file.graph), "Hall");
+        assert_eq!(graph_file.items.len(), 1);
+        assert!(!graph_file.undo().unwrap());
+    }
+
+    #[test]
+    fn the_menus_save_and_stop_when_input_runs_out() {
+        let directory = storage::scratch_directory("menus-input-runs-out");
+        let file_name = directory.join("map.json").display().to_string();
+        let mut console = ScriptedConsole::ending(&["1", &file_name, "Hall", "A dusty hall.", "14", "lamp", "A brass lamp.", "6"]);
+        run_menus(&mut console);
+        let loaded = read_graph(Path::new(&file_name), ValidationMode::Strict).unwrap();
+        assert_eq!(loaded.items.iter().map(|item| item.name.as_str()).collect::<Vec<&str>>(), vec!["lamp"]);
+        assert_eq!(loaded.graph.nodes().count(), 1);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn the_locked_door_opens_once_the_key_is_taken() {
+        let graph_file = house();
+        let mut console = ScriptedConsole::new(&["north", "take key", "north", "quit"]);
+        interactive_mode(&mut console, &graph_file);
+        let output = console.output();
+        assert!(output.contains("The door is locked."), "{}", output);
+        assert!(output.contains("You take the key."), "{}", output);
+        assert!(output.contains("Current Location: Study"), "{}", output);
+        // Playing doesn't move the editor's current location.
+        assert_eq!(current_location(&graph_file.graph), "Hall");
+    }
+
+    #[test]
+    fn deleting_a_location_deletes_its_items_until_it_is_undone() {
+        let mut graph_file = house();
+        graph_file.graph.traverse("north".to_string()).unwrap();
+        place_item(&mut ScriptedConsole::new(&["book", "A dusty book."]), &mut graph_file).unwrap();
+        graph_file.graph.reset();
+        let mut console = ScriptedConsole::new(&["2"]);
+        delete_location(&mut console, &mut graph_file).unwrap();
+        assert!(console.output().contains("The items in it were deleted too: book"), "{}", console.output());
+        assert_eq!(graph_file.items.iter().map(|item| item.name.as_str()).collect::<Vec<&str>>(), vec!["key"]);
+
+        assert!(graph_file.undo().unwrap());
+        assert_eq!(graph_file.graph.nodes().count(), 2);
+        assert_eq!(graph_file.items.iter().map(|item| item.name.as_str()).collect::<Vec<&str>>(), vec!["key", "book"]);
+        assert!(graph_file.redo().unwrap());
+        assert_eq!(graph_file.graph.nodes().count(), 1);
+        assert_eq!(graph_file.items.len(), 1);
+        // Undoing goes back through the item being placed as well.
+        assert!(graph_file.undo().unwrap());
+        assert!(graph_file.undo().unwrap());
+        assert_eq!(graph_file.items.len(), 1);
+        assert!(!graph_file.undo().unwrap());
+    }
+
+    #[test]
+    fn changes_to_items_conditions_and_locations_are_undone_in_order() {
+        let mut graph_file = house();
+        place_item(&mut ScriptedConsole::new(&["lamp", "A brass lamp."]), &mut graph_file).unwrap();
+        edit_condition(&mut ScriptedConsole::new(&["north", "f", "lit", "It's too dark."]), &mut graph_file).unwrap();
+        assert_eq!(graph_file.conditions.len(), 1);
+        assert_eq!(graph_file.conditions[0].requirement, Requirement::FlagSet { flag: "lit".to_string() });
+        remove_direction(&mut ScriptedConsole::new(&["north"]), &mut graph_file).unwrap();
+        assert!(graph_file.conditions.is_empty());
+
+        assert!(graph_file.undo().unwrap());
+        assert_eq!(graph_file.conditions[0].requirement, Requirement::FlagSet { flag: "lit".to_string() });
+        assert!(graph_file.undo().unwrap());
+        assert_eq!(graph_file.conditions[0].requirement, Requirement::HoldsItem { item_id: graph_file.items[0].id.clone() });
+        assert!(graph_file.undo().unwrap());
+        assert_eq!(graph_file.items.len(), 1);
+        assert!(!graph_file.undo().unwrap());
+        assert!(graph_file.redo().unwrap());
+        assert_eq!(graph_file.items[1].name, "lamp");
+    }
+
+    #[test]
+    fn changing_the_map_details_can_be_undone() {
+        let mut graph_file = house();
+        update_map_details(&mut ScriptedConsole::new(&["The House", "Ann"]), &mut graph_file).unwrap();
+        assert_eq!(graph_file.graph.metadata().title.as_deref(), Some("The House"));
+        update_map_details(&mut ScriptedConsole::new(&["", ""]), &mut graph_file).unwrap();
+        assert!(graph_file.undo().unwrap());
+        assert_eq!(graph_file.graph.metadata().title, None);
+        assert_eq!(graph_file.graph.metadata().author, None);
+        assert!(!graph_file.undo().unwrap());
+    }
+
+    #[test]
+    fn laying_out_a_map_that_is_already_laid_out_leaves_nothing_to_undo() {
+        let mut graph_file = house();
+        lay_out_map(&mut ScriptedConsole::new(&[]), &mut graph_file).unwrap();
+        let study = graph_file.graph.nodes().nth(1).unwrap();
+        assert_eq!(study.borrow().position, Some(Position { x: 0, y: 1, z: 0 }));
+        lay_out_map(&mut ScriptedConsole::new(&[]), &mut graph_file).unwrap();
+        assert!(graph_file.undo().unwrap());
+        assert_eq!(study.borrow().position, None);
+        assert!(!graph_file.undo().unwrap());
+    }
+
+    #[test]
+    fn old_map_files_are_migrated_when_they_are_loaded() {
+        let loaded = parse_graph(include_str!("../tests/fixtures/map_v1.json"), ValidationMode::Strict).unwrap();
+        assert_eq!(loaded.upgraded_from, Some(1));
+        assert_eq!(loaded.graph.nodes().count(), 3);
+        assert!(loaded.report.is_empty());
+        let current = parse_graph(include_str!("../tests/fixtures/map_v3.json"), ValidationMode::Strict).unwrap();
+        assert_eq!(current.upgraded_from, None);
+    }
+
+    #[test]
+    fn items_in_missing_locations_are_reported() {
+        let graph_file = house();
+        let mut report = ValidationReport::default();
+        check_items(&graph_file.graph, &graph_file.items, &mut report);
+        assert!(report.is_empty());
+        let lost = Item::new("coin".to_string(), "A gold coin.".to_string(), ItemLocation::Node("missing".to_string()));
+        check_items(&graph_file.graph, &[lost], &mut report);
+        assert_eq!(report.issues().cloned().collect::<Vec<ValidationIssue>>(), vec![
+            ValidationIssue::MisplacedItem { item: "coin".to_string(), node_id: "missing".to_string() },
+        ]);
+        assert!(report.first_error().is_none());
+    }
+
+    #[test]
+    fn conditions_go_with_what_they_depend_on_until_it_is_undone() {
+        let mut graph_file = house();
+        let mut console = ScriptedConsole::new(&["2"]);
+        delete_location(&mut console, &mut graph_file).unwrap();
+        assert!(console.output().contains("A condition that depended on it was removed as well."), "{}", console.output());
+        assert!(graph_file.conditions.is_empty());
+        assert!(graph_file.undo().unwrap());
+        assert_eq!(graph_file.conditions.len(), 1);
+
+        remove_item(&mut ScriptedConsole::new(&["key"]), &mut graph_file).unwrap();
+        assert!(graph_file.items.is_empty());
+        assert!(graph_file.conditions.is_empty());
+        assert!(graph_file.undo().unwrap());
+        assert_eq!(graph_file.conditions.len(), 1);
+    }
+
+    #[test]
+    fn conditions_that_can_never_apply_are_reported() {
+        let mut graph_file = house();
+        let hall_id = graph_file.graph.root_node_id().to_string();
+        let mut report = ValidationReport::default();
+        check_conditions(&graph_file.graph, &graph_file.items, &graph_file.conditions, &mut report);
+        assert!(report.is_empty());
+
+        graph_file.conditions[0].requirement = Requirement::FlagSet { flag: "door unlocked".to_string() };
+        graph_file.conditions.push(EdgeCondition {
+            node_id: hall_id.clone(),
+            edge: "west".to_string(),
+            requirement: Requirement::Visited { node_id: hall_id.clone() },
+            message: "The way west is blocked.".to_string(),
+        });
+        check_conditions(&graph_file.graph, &graph_file.items, &graph_file.conditions, &mut report);
+        assert_eq!(report.issues().cloned().collect::<Vec<ValidationIssue>>(), vec![
+            ValidationIssue::UnmeetableCondition { node_id: hall_id.clone(), edge: "north".to_string() },
+            ValidationIssue::ConditionOnMissingEdge { node_id: hall_id.clone(), edge: "west".to_string() },
+        ]);
+
+        // Once arriving somewhere sets the flag, the door can be opened.
+        graph_file.graph.current_node().unwrap().borrow_mut().element.sets_flags.insert("door unlocked".to_string());
+        let mut report = ValidationReport::default();
+        check_conditions(&graph_file.graph, &graph_file.items, &graph_file.conditions[..1], &mut report);
+        assert!(report.is_empty());
+    }
+}
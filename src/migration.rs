@@ -0,0 +1,277 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use serde_json::{json, Value};
+
+use crate::graph::FORMAT_VERSION;
+
+/// The ways that upgrading a map file to the current format can fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MigrationError {
+    /// The data isn't shaped like any known version of the map file format.
+    UnknownFormat,
+    /// The map was written by a newer version of the game than this one.
+    UnsupportedVersion(u32),
+    /// There isn't a registered step to upgrade from the provided version.
+    MissingStep(u32),
+    /// An upgrade step couldn't make sense of the data it was given.
+    InvalidData { from_version: u32, reason: String },
+}
+
+impl Display for MigrationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationError::UnknownFormat => write!(f, "The data isn't in a known map file format."),
+            MigrationError::UnsupportedVersion(version) =>
+                write!(f, "The map uses format version {}, but only versions up to {} are supported.", version, FORMAT_VERSION),
+            MigrationError::MissingStep(version) => write!(f, "There isn't a way to upgrade from format version {}.", version),
+            MigrationError::InvalidData { from_version, reason } =>
+                write!(f, "The map couldn't be upgraded from format version {}: {}", from_version, reason),
+        }
+    }
+}
+
+impl Error for MigrationError {}
+
+/// Upgrades the data for a map file from `from_version` to the next version. The step doesn't need
+/// to update `format_version`, since that's taken care of by `migrate`.
+struct Migration {
+    from_version: u32,
+    upgrade: fn(Value) -> Result<Value, String>,
+}
+
+/// Every registered upgrade step, in version order. When the shape of a map file changes, bump
+/// `graph::FORMAT_VERSION`, add a step from the previous version here and add a fixture for the
+/// previous version to `tests/fixtures`.
+const MIGRATIONS: &[Migration] = &[
+    Migration { from_version: 1, upgrade: wrap_in_envelope },
+    Migration { from_version: 2, upgrade: locations_from_descriptions },
+];
+
+/// The result of upgrading the data for a map file to the current format.
+pub struct Migrated {
+    /// The upgraded data.
+    pub value: Value,
+    /// The version that the data was originally written in.
+    pub from_version: u32,
+}
+
+impl Migrated {
+    /// Whether the data had to be upgraded, meaning that the file is in an older format.
+    pub fn was_upgraded(&self) -> bool {
+        self.from_version < FORMAT_VERSION
+    }
+}
+
+/// Find the format version that the data for a map file was written in. Version 1 files were a
+/// bare array of nodes, while later versions record the version in the file itself.
+pub fn version_of(value: &Value) -> Result<u32, MigrationError> {
+    match value {
+        Value::Array(_) => Ok(1),
+        Value::Object(fields) => match fields.get("format_version").and_then(Value::as_u64) {
+            Some(version) => u32::try_from(version).map_err(|_| MigrationError::UnsupportedVersion(u32::MAX)),
+            None => Err(MigrationError::UnknownFormat),
+        },
+        _ => Err(MigrationError::UnknownFormat),
+    }
+}
+
+/// Upgrade the data for a map file to the current format by running every step from its version
+/// onwards in order.
+pub fn migrate(mut value: Value) -> Result<Migrated, MigrationError> {
+    let from_version = version_of(&value)?;
+    if from_version > FORMAT_VERSION {
+        return Err(MigrationError::UnsupportedVersion(from_version));
+    }
+    for version in from_version..FORMAT_VERSION {
+        let migration = match MIGRATIONS.iter().find(|migration| migration.from_version == version) {
+            Some(migration) => migration,
+            None => return Err(MigrationError::MissingStep(version)),
+        };
+        value = (migration.upgrade)(value)
+            .map_err(|reason| MigrationError::InvalidData { from_version: version, reason })?;
+        if let Value::Object(fields) = &mut value {
+            fields.insert("format_version".to_string(), json!(version + 1));
+        }
+    }
+    Ok(Migrated { value, from_version })
+}
+
+/// Version 1 to 2: the bare array of nodes moved into an envelope that records the root and
+/// current position explicitly. The root was always the first node.
+fn wrap_in_envelope(value: Value) -> Result<Value, String> {
+    let root_node_id = match value.get(0).and_then(|root_node| root_node.get("id")) {
+        Some(root_node_id) => root_node_id.clone(),
+        None => return Err("there isn't a root node".to_string()),
+    };
+    Ok(json!({
+        "root_node_id": root_node_id,
+        "current_node_id": root_node_id,
+        "metadata": {},
+        "nodes": value,
+    }))
+}
+
+/// Version 2 to 3: node elements changed from a description string to a Location. The description
+/// is kept as it was, and its first sentence becomes the name.
+fn locations_from_descriptions(mut value: Value) -> Result<Value, String> {
+    let nodes = match value.get_mut("nodes").and_then(Value::as_array_mut) {
+        Some(nodes) => nodes,
+        None => return Err("there isn't a list of nodes".to_string()),
+    };
+    for node in nodes {
+        let description = match node.get("element").and_then(Value::as_str) {
+            Some(description) => description.to_string(),
+            None => return Err("a node's element isn't a description".to_string()),
+        };
+        node["element"] = json!({
+            "name": name_from_description(&description),
+            "description": description,
+        });
+    }
+    Ok(value)
+}
+
+fn name_from_description(description: &str) -> String {
+    let first_sentence = description.split(['.', '!', '?', '\n']).next().unwrap_or_default().trim();
+    if first_sentence.is_empty() {
+        description.trim().to_string()
+    } else {
+        first_sentence.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::graph::{FORMAT_VERSION, ValidationMode};
+    use crate::location::LocationGraph;
+
+    use super::*;
+
+    /// A map file for every historical format version, all describing the same three locations.
+    const FIXTURES: &[(u32, &str)] = &[
+        (1, include_str!("../tests/fixtures/map_v1.json")),
+        (2, include_str!("../tests/fixtures/map_v2.json")),
+        (3, include_str!("../tests/fixtures/map_v3.json")),
+    ];
+
+    const HALL_ID: &str = "6f1c2a4e-0b7d-4f0e-9a51-3d2f1c7b8e01";
+
+    fn load(data: &str) -> (LocationGraph, u32) {
+        let migrated = migrate(serde_json::from_str(data).unwrap()).unwrap();
+        let (graph, report) = LocationGraph::deserialize_with_mode(&migrated.value, ValidationMode::Strict).unwrap();
+        assert!(report.is_empty());
+        (graph, migrated.from_version)
+    }
+
+    #[test]
+    fn every_version_has_a_fixture() {
+        for version in 1..=FORMAT_VERSION {
+            assert!(FIXTURES.iter().any(|(fixture_version, _)| *fixture_version == version), "missing fixture for version {}", version);
+        }
+    }
+
+    #[test]
+    fn fixtures_are_detected_as_their_version() {
+        for (version, data) in FIXTURES {
+            assert_eq!(version_of(&serde_json::from_str(data).unwrap()), Ok(*version));
+        }
+    }
+
+    #[test]
+    fn fixtures_migrate_to_the_current_version() {
+        for (version, data) in FIXTURES {
+            let migrated = migrate(serde_json::from_str(data).unwrap()).unwrap();
+            assert_eq!(migrated.from_version, *version);
+            assert_eq!(migrated.was_upgraded(), *version < FORMAT_VERSION);
+            assert_eq!(version_of(&migrated.value), Ok(FORMAT_VERSION));
+        }
+    }
+
+    #[test]
+    fn fixtures_load_with_their_locations_and_directions() {
+        for (_, data) in FIXTURES {
+            let (mut graph, _) = load(data);
+            assert_eq!(graph.nodes().count(), 3);
+            graph.reset();
+            assert_eq!(graph.current_node().unwrap().borrow().id, HALL_ID);
+            assert_eq!(graph.traverse("east".to_string()).unwrap().borrow().element.description, "A quiet library.");
+            assert_eq!(graph.traverse("west".to_string()).unwrap().borrow().id, HALL_ID);
+        }
+    }
+
+    #[test]
+    fn version_1_starts_at_the_first_node() {
+        let (graph, _) = load(FIXTURES[0].1);
+        assert_eq!(graph.current_node().unwrap().borrow().id, HALL_ID);
+        assert_eq!(graph.metadata().title, None);
+    }
+
+    #[test]
+    fn version_2_keeps_position_and_metadata() {
+        let (graph, _) = load(FIXTURES[1].1);
+        assert_eq!(graph.current_node().unwrap().borrow().element.description, "A cramped kitchen.");
+        assert_eq!(graph.metadata().title.as_deref(), Some("The Old House"));
+        assert_eq!(graph.metadata().author.as_deref(), Some("A. Writer"));
+    }
+
+    #[test]
+    fn version_2_descriptions_become_locations() {
+        let (graph, _) = load(FIXTURES[1].1);
+        let hall = graph.node(HALL_ID).unwrap();
+        let hall = &hall.borrow().element;
+        assert_eq!(hall.name, "A dusty entrance hall");
+        assert_eq!(hall.description, "A dusty entrance hall.");
+        assert_eq!(hall.first_visit_description, None);
+        assert!(hall.tags.is_empty());
+    }
+
+    #[test]
+    fn version_3_keeps_every_location_field() {
+        let (graph, _) = load(FIXTURES[2].1);
+        let hall = graph.node(HALL_ID).unwrap();
+        let hall = &hall.borrow().element;
+        assert_eq!(hall.name, "Entrance Hall");
+        assert_eq!(hall.description_for_visit(true), "You push open the heavy door and step into a dusty entrance hall.");
+        assert_eq!(hall.description_for_visit(false), "A dusty entrance hall.");
+        assert!(hall.tags.contains("ground floor"));
+        assert_eq!(hall.properties.get("lighting").map(String::as_str), Some("dim"));
+    }
+
+    #[test]
+    fn names_are_the_first_sentence_of_the_description() {
+        assert_eq!(name_from_description("A dark cave. Water drips."), "A dark cave");
+        assert_eq!(name_from_description("Where am I?"), "Where am I");
+        assert_eq!(name_from_description("No punctuation"), "No punctuation");
+        assert_eq!(name_from_description("..."), "...");
+    }
+
+    #[test]
+    fn migrated_maps_round_trip_in_the_current_format() {
+        for (_, data) in FIXTURES {
+            let (graph, _) = load(data);
+            let (reloaded, from_version) = load(&serde_json::to_string(&graph).unwrap());
+            assert_eq!(from_version, FORMAT_VERSION);
+            assert_eq!(reloaded.nodes().count(), graph.nodes().count());
+        }
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let value: Value = serde_json::from_str(r#"{"format_version": 999, "nodes": []}"#).unwrap();
+        assert_eq!(migrate(value).err(), Some(MigrationError::UnsupportedVersion(999)));
+    }
+
+    #[test]
+    fn unknown_shapes_are_rejected() {
+        assert_eq!(migrate(Value::String("map".to_string())).err(), Some(MigrationError::UnknownFormat));
+        assert_eq!(migrate(serde_json::from_str(r#"{"nodes": []}"#).unwrap()).err(), Some(MigrationError::UnknownFormat));
+    }
+
+    #[test]
+    fn empty_version_1_maps_are_rejected() {
+        assert!(matches!(migrate(Value::Array(vec![])), Err(MigrationError::InvalidData { from_version: 1, .. })));
+    }
+}
@@ -0,0 +1,388 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use crate::graph::{CaseInsensitive, Direction, EdgeMatch, EdgeMatcher, EditDistance, Exact, Fallback, UniquePrefix, edit_distance};
+
+/// Something that the player can do in interactive mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verb {
+    Go,
+    GoTo,
+    Look,
+    Examine,
+    Take,
+    Drop,
+    Inventory,
+    Map,
+    Save,
+    Load,
+    Saves,
+    Help,
+    Quit,
+}
+
+impl Verb {
+    /// Every verb, in the order that they're listed in the help.
+    pub const ALL: [Verb; 13] = [
+        Verb::Go, Verb::GoTo, Verb::Look, Verb::Examine, Verb::Take, Verb::Drop, Verb::Inventory, Verb::Map,
+        Verb::Save, Verb::Load, Verb::Saves, Verb::Help, Verb::Quit,
+    ];
+
+    /// How the verb is used, for the help.
+    pub fn usage(&self) -> &'static str {
+        match self {
+            Verb::Go => "go <direction>, or just the direction",
+            Verb::GoTo => "go to <location>",
+            Verb::Look => "look",
+            Verb::Examine => "examine <item>",
+            Verb::Take => "take <item>",
+            Verb::Drop => "drop <item>",
+            Verb::Inventory => "inventory",
+            Verb::Map => "map [how many steps away]",
+            Verb::Save => "save <slot>",
+            Verb::Load => "load <slot>",
+            Verb::Saves => "saves",
+            Verb::Help => "help",
+            Verb::Quit => "quit",
+        }
+    }
+
+    /// Whether the verb needs a noun to say what it applies to.
+    fn needs_noun(&self) -> bool {
+        matches!(self, Verb::Go | Verb::GoTo | Verb::Examine | Verb::Take | Verb::Drop | Verb::Save | Verb::Load)
+    }
+
+    /// What the player is asked when they leave out the noun.
+    fn missing_noun_question(&self) -> &'static str {
+        match self {
+            Verb::Go => "Which way do you want to go?",
+            Verb::GoTo => "Where do you want to go?",
+            Verb::Examine => "What do you want to examine?",
+            Verb::Take => "What do you want to take?",
+            Verb::Drop => "What do you want to drop?",
+            Verb::Save => "Which slot do you want to save to?",
+            Verb::Load => "Which slot do you want to load?",
+            _ => "What do you want to do?",
+        }
+    }
+}
+
+/// The phrases that are understood as each verb when there isn't a custom table.
+const DEFAULT_VERBS: &[(&str, Verb)] = &[
+    ("go", Verb::Go), ("walk", Verb::Go), ("move", Verb::Go), ("head", Verb::Go),
+    ("go to", Verb::GoTo), ("walk to", Verb::GoTo), ("travel to", Verb::GoTo),
+    ("look", Verb::Look), ("l", Verb::Look),
+    ("examine", Verb::Examine), ("inspect", Verb::Examine), ("look at", Verb::Examine), ("read", Verb::Examine),
+    ("take", Verb::Take), ("get", Verb::Take), ("grab", Verb::Take), ("pick up", Verb::Take),
+    ("drop", Verb::Drop), ("put down", Verb::Drop),
+    ("inventory", Verb::Inventory), ("inv", Verb::Inventory), ("i", Verb::Inventory),
+    ("map", Verb::Map),
+    ("save", Verb::Save),
+    ("load", Verb::Load), ("restore", Verb::Load),
+    ("saves", Verb::Saves),
+    ("help", Verb::Help), ("?", Verb::Help),
+    ("quit", Verb::Quit), ("exit", Verb::Quit), ("q", Verb::Quit), ("x", Verb::Quit),
+];
+
+/// Words that don't change the meaning of a noun and are dropped from the start of it.
+const ARTICLES: &[&str] = &["the", "a", "an"];
+
+/// How many edits a word can be away from a known word for it to be suggested.
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// How many suggestions are offered at most.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// A command that the player entered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    pub verb: Verb,
+    /// What the verb applies to, with any articles removed. For `Verb::Go`, this is the direction
+    /// as it's written in the map.
+    pub noun: Option<String>,
+}
+
+/// The ways that the player's input can fail to make sense.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// Nothing was entered.
+    Empty,
+    /// The input doesn't start with a known verb or direction.
+    Unknown { input: String, suggestions: Vec<String> },
+    /// The player tried to go in a direction that doesn't lead anywhere from their location.
+    UnknownDirection { direction: String, suggestions: Vec<String> },
+    /// The direction could be more than one of the directions from the player's location.
+    AmbiguousDirection { direction: String, candidates: Vec<String> },
+    /// The verb needs a noun, but there wasn't one.
+    MissingNoun(Verb),
+}
+
+fn write_suggestions(f: &mut Formatter<'_>, suggestions: &[String]) -> std::fmt::Result {
+    if suggestions.is_empty() {
+        write!(f, " Enter \"help\" to see what you can do.")
+    } else {
+        write!(f, " Did you mean: {}?", suggestions.join(", "))
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "Enter a command, or \"help\" to see what you can do."),
+            ParseError::Unknown { input, suggestions } => {
+                write!(f, "I don't understand \"{}\".", input)?;
+                write_suggestions(f, suggestions)
+            }
+            ParseError::UnknownDirection { direction, suggestions } => {
+                write!(f, "You can't go \"{}\" from here.", direction)?;
+                write_suggestions(f, suggestions)
+            }
+            ParseError::AmbiguousDirection { direction, candidates } =>
+                write!(f, "\"{}\" could be more than one direction. Did you mean: {}?", direction, candidates.join(", ")),
+            ParseError::MissingNoun(verb) => write!(f, "{}", verb.missing_noun_question()),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Turns what the player types into Commands using a table of verb phrases. Phrases can be more than
+/// one word, and the longest phrase that starts the input wins.
+pub struct Parser {
+    verbs: Vec<(Vec<String>, Verb)>,
+    /// Recognises a direction that's entered on its own, before the input is treated as a verb.
+    bare_direction_matcher: Fallback<String>,
+    /// Finds the direction that the player means after "go", or when the input isn't a verb.
+    direction_matcher: Fallback<String>,
+}
+
+impl Parser {
+    /// A Parser that understands the default verb phrases.
+    pub fn new() -> Self {
+        let mut parser = Parser {
+            verbs: Vec::new(),
+            bare_direction_matcher: Fallback(vec![Box::new(Exact), Box::new(CaseInsensitive), Box::new(Compass)]),
+            direction_matcher: Fallback(vec![Box::new(Exact), Box::new(CaseInsensitive), Box::new(Compass), Box::new(UniquePrefix)]),
+        };
+        for (phrase, verb) in DEFAULT_VERBS {
+            parser.add_verb(phrase, *verb);
+        }
+        parser
+    }
+
+    /// Understand the phrase as the verb, in addition to any phrases that it's already known by.
+    pub fn add_verb(&mut self, phrase: &str, verb: Verb) {
+        let words = phrase.split_whitespace().map(str::to_lowercase).collect::<Vec<String>>();
+        if !words.is_empty() {
+            self.verbs.push((words, verb));
+        }
+    }
+
+    /// How the Parser decides which direction the player means.
+    pub fn direction_matcher(&self) -> &dyn EdgeMatcher<String> {
+        &self.direction_matcher
+    }
+
+    /// Every phrase that's understood as the verb.
+    pub fn phrases(&self, verb: Verb) -> Vec<String> {
+        self.verbs.iter()
+            .filter(|(_, phrase_verb)| *phrase_verb == verb)
+            .map(|(words, _)| words.join(" "))
+            .collect()
+    }
+
+    /// Work out what the player wants to do. `directions` are the directions that lead out of the
+    /// player's location, which can be entered without a verb.
+    pub fn parse(&self, input: &str, directions: &[String]) -> Result<Command, ParseError> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Err(ParseError::Empty);
+        }
+        match self.bare_direction_matcher.find(input, directions) {
+            EdgeMatch::Found(direction) => return Ok(Command { verb: Verb::Go, noun: Some(direction) }),
+            EdgeMatch::Ambiguous(candidates) => return Err(ambiguous_direction(input, candidates)),
+            EdgeMatch::NotFound => (),
+        }
+        let words = input.split_whitespace().collect::<Vec<&str>>();
+        let (verb, phrase_length) = match self.match_verb(&words) {
+            Some(matched) => matched,
+            // Anything that isn't a verb might still be the start of a direction.
+            None => return match self.find_direction(input, directions) {
+                Ok(direction) => Ok(Command { verb: Verb::Go, noun: Some(direction) }),
+                Err(ParseError::UnknownDirection { .. }) =>
+                    Err(ParseError::Unknown { input: input.to_string(), suggestions: self.suggestions(&words, directions) }),
+                Err(e) => Err(e),
+            },
+        };
+        let entered_noun_words = &words[phrase_length..];
+        // Only a leading article is dropped, so names like "Hall of the Mountain King" stay whole.
+        let noun_words = match entered_noun_words.split_first() {
+            Some((first_word, rest)) if ARTICLES.iter().any(|article| first_word.eq_ignore_ascii_case(article)) => rest,
+            _ => entered_noun_words,
+        };
+        let noun = (!noun_words.is_empty()).then(|| noun_words.join(" "));
+        match (verb, noun) {
+            // Looking at something is the same as examining it.
+            (Verb::Look, Some(noun)) => Ok(Command { verb: Verb::Examine, noun: Some(noun) }),
+            // A direction can start with an article itself, as in "the stairs".
+            (Verb::Go, Some(noun)) => match self.find_direction(&entered_noun_words.join(" "), directions) {
+                Ok(direction) => Ok(Command { verb: Verb::Go, noun: Some(direction) }),
+                Err(_) => Ok(Command { verb: Verb::Go, noun: Some(self.find_direction(&noun, directions)?) }),
+            },
+            (verb, None) if verb.needs_noun() => Err(ParseError::MissingNoun(verb)),
+            (verb, noun) => Ok(Command { verb, noun }),
+        }
+    }
+
+    /// The verb for the longest phrase that the words start with, along with how many words the
+    /// phrase has.
+    fn match_verb(&self, words: &[&str]) -> Option<(Verb, usize)> {
+        self.verbs.iter()
+            .filter(|(phrase, _)| phrase.len() <= words.len()
+                && phrase.iter().zip(words).all(|(phrase_word, word)| phrase_word.eq_ignore_ascii_case(word)))
+            .max_by_key(|(phrase, _)| phrase.len())
+            .map(|(phrase, verb)| (*verb, phrase.len()))
+    }
+
+    /// The direction in `directions` that the input refers to.
+    fn find_direction(&self, input: &str, directions: &[String]) -> Result<String, ParseError> {
+        match self.direction_matcher.find(input, directions) {
+            EdgeMatch::Found(direction) => Ok(direction),
+            EdgeMatch::Ambiguous(candidates) => Err(ambiguous_direction(input, candidates)),
+            EdgeMatch::NotFound => {
+                let suggestions = match (EditDistance { max_distance: MAX_SUGGESTION_DISTANCE }).find(input, directions) {
+                    EdgeMatch::Found(direction) => vec![direction],
+                    EdgeMatch::Ambiguous(candidates) => candidates,
+                    EdgeMatch::NotFound => Vec::new(),
+                };
+                Err(ParseError::UnknownDirection { direction: input.to_string(), suggestions })
+            }
+        }
+    }
+
+    /// Known verb phrases and directions that look like what the player might have meant.
+    fn suggestions(&self, words: &[&str], directions: &[String]) -> Vec<String> {
+        let first_word = words[0];
+        let phrases = self.verbs.iter().map(|(phrase, _)| phrase.join(" ")).collect::<Vec<String>>();
+        closest(first_word, phrases.iter().chain(directions).map(String::as_str))
+    }
+}
+
+/// Matches built-in directions however they're written, so "n" finds "North" and "north-east" finds
+/// "northeast".
+struct Compass;
+
+impl EdgeMatcher<String> for Compass {
+    fn find(&self, input: &str, edges: &[String]) -> EdgeMatch<String> {
+        match input.parse::<Direction>() {
+            Ok(compass_direction) => EdgeMatch::from_candidates(edges.iter()
+                .filter(|edge| edge.parse::<Direction>() == Ok(compass_direction))
+                .cloned()
+                .collect()),
+            Err(_) => EdgeMatch::NotFound,
+        }
+    }
+}
+
+fn ambiguous_direction(input: &str, mut candidates: Vec<String>) -> ParseError {
+    candidates.sort();
+    ParseError::AmbiguousDirection { direction: input.to_string(), candidates }
+}
+
+/// The candidates that are closest to the word, as long as they're close enough to be worth
+/// suggesting.
+fn closest<'a>(word: &str, candidates: impl Iterator<Item=&'a str>) -> Vec<String> {
+    let word = word.to_lowercase();
+    let mut scored = candidates
+        .map(|candidate| (edit_distance(&word, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, candidate)| *distance <= MAX_SUGGESTION_DISTANCE && *distance < candidate.chars().count())
+        .collect::<Vec<(usize, &str)>>();
+    scored.sort();
+    let mut suggestions: Vec<String> = Vec::new();
+    for (_, candidate) in scored {
+        if !suggestions.iter().any(|suggestion| suggestion == candidate) {
+            suggestions.push(candidate.to_string());
+        }
+    }
+    suggestions.truncate(MAX_SUGGESTIONS);
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directions() -> Vec<String> {
+        vec!["north".to_string(), "South-West".to_string(), "Up the stairs".to_string()]
+    }
+
+    fn parse(input: &str) -> Result<Command, ParseError> {
+        Parser::new().parse(input, &directions())
+    }
+
+    fn command(verb: Verb, noun: Option<&str>) -> Result<Command, ParseError> {
+        Ok(Command { verb, noun: noun.map(String::from) })
+    }
+
+    #[test]
+    fn directions_can_be_entered_on_their_own_or_abbreviated() {
+        assert_eq!(parse("north"), command(Verb::Go, Some("north")));
+        assert_eq!(parse("N"), command(Verb::Go, Some("north")));
+        assert_eq!(parse("go north"), command(Verb::Go, Some("north")));
+        assert_eq!(parse("walk n"), command(Verb::Go, Some("north")));
+        assert_eq!(parse("up the stairs"), command(Verb::Go, Some("Up the stairs")));
+        assert_eq!(parse("sw"), command(Verb::Go, Some("South-West")));
+        assert_eq!(parse("go southwest"), command(Verb::Go, Some("South-West")));
+    }
+
+    #[test]
+    fn the_longest_verb_phrase_wins() {
+        assert_eq!(parse("go to the library"), command(Verb::GoTo, Some("library")));
+        assert_eq!(parse("look at the lamp"), command(Verb::Examine, Some("lamp")));
+        assert_eq!(parse("pick up a brass key"), command(Verb::Take, Some("brass key")));
+        assert_eq!(parse("look"), command(Verb::Look, None));
+        assert_eq!(parse("Look Lamp"), command(Verb::Examine, Some("Lamp")));
+    }
+
+    #[test]
+    fn directions_can_be_shortened_while_they_are_unique() {
+        assert_eq!(parse("go nor"), command(Verb::Go, Some("north")));
+        assert_eq!(parse("up"), command(Verb::Go, Some("Up the stairs")));
+        let directions = vec!["north".to_string(), "north-east".to_string()];
+        assert_eq!(Parser::new().parse("go no", &directions),
+                   Err(ParseError::AmbiguousDirection { direction: "no".to_string(), candidates: directions.clone() }));
+    }
+
+    #[test]
+    fn only_a_leading_article_is_dropped_from_nouns() {
+        assert_eq!(parse("go up the stairs"), command(Verb::Go, Some("Up the stairs")));
+        assert_eq!(parse("go to the Hall of the Mountain King"), command(Verb::GoTo, Some("Hall of the Mountain King")));
+        assert_eq!(parse("take a box of matches"), command(Verb::Take, Some("box of matches")));
+        let directions = vec!["the stairs".to_string(), "through a door".to_string()];
+        assert_eq!(Parser::new().parse("go the stairs", &directions), command(Verb::Go, Some("the stairs")));
+        assert_eq!(Parser::new().parse("go through a door", &directions), command(Verb::Go, Some("through a door")));
+        assert_eq!(Parser::new().parse("go the thr", &directions), command(Verb::Go, Some("through a door")));
+    }
+
+    #[test]
+    fn verbs_without_a_needed_noun_ask_for_one() {
+        assert_eq!(parse("take the"), Err(ParseError::MissingNoun(Verb::Take)));
+        assert_eq!(parse("go"), Err(ParseError::MissingNoun(Verb::Go)));
+    }
+
+    #[test]
+    fn unknown_input_gets_suggestions() {
+        assert_eq!(parse("nrth"), Err(ParseError::Unknown { input: "nrth".to_string(), suggestions: vec!["north".to_string()] }));
+        assert_eq!(parse("go nrth"), Err(ParseError::UnknownDirection { direction: "nrth".to_string(), suggestions: vec!["north".to_string()] }));
+        assert_eq!(parse("exmine lamp"), Err(ParseError::Unknown { input: "exmine lamp".to_string(), suggestions: vec!["examine".to_string()] }));
+        assert_eq!(parse("xyzzy"), Err(ParseError::Unknown { input: "xyzzy".to_string(), suggestions: vec![] }));
+    }
+
+    #[test]
+    fn verbs_can_be_added() {
+        let mut parser = Parser::new();
+        parser.add_verb("Peer At", Verb::Examine);
+        assert_eq!(parser.parse("peer at the lamp", &[]), command(Verb::Examine, Some("lamp")));
+        assert!(parser.phrases(Verb::Examine).contains(&"peer at".to_string()));
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::item::{Item, ItemLocation};
+use crate::location::LocationGraph;
+use crate::storage;
+
+const SAVE_EXTENSION: &str = ".save";
+/// What the player is told when a save slot name isn't allowed.
+pub const INVALID_SLOT_NAME: &str = "Save slot names can only contain letters, numbers, dashes and underscores.";
+
+/// The state of a game that's being played on a map, kept separately from the map itself so that
+/// playing never changes the map file.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SaveGame {
+    /// The file name of the map that the game is being played on.
+    pub map_file: String,
+    /// A hash of the map's locations and directions when the game was saved, used to notice when
+    /// the map has been edited since.
+    pub map_hash: String,
+    /// The id of the location that the player is at.
+    pub current_node_id: String,
+    /// The ids of every location that the player has been to.
+    pub visited: BTreeSet<String>,
+    /// How many moves the player has made.
+    pub turns: u32,
+    /// Named flags that have been set during the game.
+    pub flags: BTreeSet<String>,
+    /// Where every item that the player has moved is now, keyed by item id. Items that aren't
+    /// listed are still where the map puts them.
+    #[serde(default)]
+    pub moved_items: BTreeMap<String, ItemLocation>,
+}
+
+impl SaveGame {
+    /// Start a new game on the map at the provided location.
+    pub fn new(map_file: &str, graph: &LocationGraph, items: &[Item], start_node_id: String) -> Self {
+        let mut game = SaveGame {
+            map_file: map_file.to_string(),
+            map_hash: map_hash(graph, items),
+            visited: BTreeSet::from([start_node_id.clone()]),
+            current_node_id: start_node_id.clone(),
+            turns: 0,
+            flags: BTreeSet::new(),
+            moved_items: BTreeMap::new(),
+        };
+        game.set_flags_for(graph, &start_node_id);
+        game
+    }
+
+    /// Record that the player has moved to the location for the provided id, setting the flags
+    /// that arriving there sets.
+    pub fn move_to(&mut self, graph: &LocationGraph, node_id: String) {
+        self.set_flags_for(graph, &node_id);
+        self.visited.insert(node_id.clone());
+        self.current_node_id = node_id;
+        self.turns += 1;
+    }
+
+    fn set_flags_for(&mut self, graph: &LocationGraph, node_id: &str) {
+        if let Ok(node) = graph.node(node_id) {
+            self.flags.extend(node.borrow().element.sets_flags.iter().cloned());
+        }
+    }
+
+    /// Where the item is in this game.
+    pub fn item_location<'a>(&'a self, item: &'a Item) -> &'a ItemLocation {
+        self.moved_items.get(&item.id).unwrap_or(&item.location)
+    }
+
+    /// Record that the item has been moved somewhere else.
+    pub fn move_item(&mut self, item: &Item, location: ItemLocation) {
+        self.moved_items.insert(item.id.clone(), location);
+    }
+
+    /// Whether the map has been edited since the game was saved.
+    pub fn map_changed(&self, graph: &LocationGraph, items: &[Item]) -> bool {
+        self.map_hash != map_hash(graph, items)
+    }
+
+    /// Write the game to the named slot for its map, replacing whatever was saved there.
+    pub fn save(&mut self, graph: &LocationGraph, items: &[Item], slot: &str) -> Result<(), String> {
+        self.map_hash = map_hash(graph, items);
+        let data = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        storage::write_atomic(&slot_path(&self.map_file, slot)?, data.as_bytes(), 0).map_err(|e| e.to_string())
+    }
+
+    /// Read the game saved in the named slot for the map.
+    pub fn load(map_file: &str, slot: &str) -> Result<Self, String> {
+        let data = fs::read_to_string(slot_path(map_file, slot)?).map_err(|e| e.to_string())?;
+        serde_json::from_str(data.as_str()).map_err(|e| e.to_string())
+    }
+}
+
+/// Whether the name can be used for a save slot. Only letters, numbers, dashes and underscores are
+/// allowed, so that the name is safe to use as part of a file name.
+pub fn is_valid_slot_name(slot: &str) -> bool {
+    !slot.is_empty() && slot.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// The path of the file for a save slot, which lives next to the map file.
+fn slot_path(map_file: &str, slot: &str) -> Result<PathBuf, String> {
+    if !is_valid_slot_name(slot) {
+        return Err(INVALID_SLOT_NAME.to_string());
+    }
+    Ok(PathBuf::from(format!("{}.{}{}", map_file, slot, SAVE_EXTENSION)))
+}
+
+/// The names of every save slot for the map, in alphabetical order.
+pub fn slots(map_file: &str) -> Vec<String> {
+    let map_path = Path::new(map_file);
+    let prefix = match map_path.file_name().and_then(|file_name| file_name.to_str()) {
+        Some(file_name) => format!("{}.", file_name),
+        None => return Vec::new(),
+    };
+    let directory = match map_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut slots = match fs::read_dir(directory) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter_map(|file_name| file_name.strip_prefix(&prefix)?.strip_suffix(SAVE_EXTENSION).map(String::from))
+            .collect::<Vec<String>>(),
+        Err(_) => Vec::new(),
+    };
+    slots.sort();
+    slots
+}
+
+/// A hash of the locations, directions and items in the map. The position in the editor and the
+/// metadata aren't included, since they don't change what's being played.
+pub fn map_hash(graph: &LocationGraph, items: &[Item]) -> String {
+    // FNV-1a, which is stable across releases unlike the standard library's hasher.
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut write = |text: &str| {
+        for byte in text.bytes().chain([0]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+    for node in graph.nodes() {
+        let node = node.borrow();
+        write(&node.id);
+        write(&serde_json::to_string(&node.element).unwrap_or_default());
+        // Edges are sorted so that only where they lead counts, not the order they were added in.
+        let mut edges = node.edges().collect::<Vec<(&String, &String)>>();
+        edges.sort();
+        for (edge, target_id) in edges {
+            write(edge);
+            write(target_id);
+        }
+    }
+    for item in items {
+        write(&serde_json::to_string(item).unwrap_or_default());
+    }
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::location::{Location, LocationNode};
+    use crate::storage::scratch_directory;
+
+    /// A hall with a key in it, where the map file is in the directory.
+    fn house(directory: &Path) -> (String, LocationGraph, Vec<Item>) {
+        let graph = LocationGraph::new(Location::new("Hall".to_string(), "A dusty hall.".to_string()));
+        let key = Item::new("key".to_string(), "A brass key.".to_string(), ItemLocation::Node(graph.root_node_id().to_string()));
+        (directory.join("house.json").to_str().unwrap().to_string(), graph, vec![key])
+    }
+
+    #[test]
+    fn games_are_saved_and_loaded_by_slot() {
+        let directory = scratch_directory("save-slots");
+        let (map_file, graph, items) = house(&directory);
+        assert!(slots(&map_file).is_empty());
+        let mut game = SaveGame::new(&map_file, &graph, &items, graph.root_node_id().to_string());
+        game.move_item(&items[0], ItemLocation::Inventory);
+        game.turns = 3;
+        game.save(&graph, &items, "quick").unwrap();
+        game.save(&graph, &items, "before-the-cellar").unwrap();
+        assert!(game.save(&graph, &items, "before the cellar").is_err());
+        // Other files next to the map aren't slots.
+        fs::write(directory.join("house.json.bak.1"), "").unwrap();
+        assert_eq!(slots(&map_file), vec!["before-the-cellar", "quick"]);
+
+        let loaded = SaveGame::load(&map_file, "quick").unwrap();
+        assert_eq!(loaded.turns, 3);
+        assert_eq!(loaded.current_node_id, graph.root_node_id());
+        assert_eq!(loaded.item_location(&items[0]), &ItemLocation::Inventory);
+        assert!(SaveGame::load(&map_file, "missing").is_err());
+        assert!(SaveGame::load(&map_file, "../quick").is_err());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn arriving_at_a_location_sets_its_flags() {
+        let (map_file, mut graph, items) = house(Path::new("missing-directory"));
+        let mut cellar = Location::new("Cellar".to_string(), "A damp cellar.".to_string());
+        cellar.sets_flags.insert("found the cellar".to_string());
+        let cellar = LocationNode::new(cellar);
+        let cellar_id = cellar.id.clone();
+        graph.insert_node(Rc::new(RefCell::new(cellar))).unwrap();
+
+        let mut game = SaveGame::new(&map_file, &graph, &items, graph.root_node_id().to_string());
+        assert!(game.flags.is_empty());
+        game.move_to(&graph, cellar_id.clone());
+        assert_eq!(game.flags, BTreeSet::from(["found the cellar".to_string()]));
+        // Starting in a location sets its flags as well.
+        assert_eq!(SaveGame::new(&map_file, &graph, &items, cellar_id).flags, game.flags);
+    }
+
+    #[test]
+    fn editing_what_is_played_changes_the_map() {
+        let (map_file, mut graph, mut items) = house(Path::new("missing-directory"));
+        let game = SaveGame::new(&map_file, &graph, &items, graph.root_node_id().to_string());
+        assert!(!game.map_changed(&graph, &items));
+
+        // The metadata and the position in the editor don't change what's played.
+        graph.metadata_mut().title = Some("The House".to_string());
+        graph.reset();
+        assert!(!game.map_changed(&graph, &items));
+
+        items[0].description = "A rusty key.".to_string();
+        assert!(game.map_changed(&graph, &items));
+        items[0].description = "A brass key.".to_string();
+        graph.current_node().unwrap().borrow_mut().element.description = "A clean hall.".to_string();
+        assert!(game.map_changed(&graph, &items));
+    }
+}
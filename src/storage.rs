@@ -0,0 +1,127 @@
+use std::ffi::OsString;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// How many previous versions of a map file are kept alongside it.
+pub const BACKUP_COUNT: usize = 5;
+
+/// The path of a sibling file that has the provided suffix appended to the file name.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name: OsString = path.file_name().map(|file_name| file_name.to_os_string()).unwrap_or_default();
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
+
+/// The path of a backup of the file, where generation 1 is the most recent.
+pub fn backup_path(path: &Path, generation: usize) -> PathBuf {
+    with_suffix(path, &format!(".bak.{}", generation))
+}
+
+/// The paths of every backup of the file that exists, from the most recent to the oldest.
+pub fn backups(path: &Path) -> Vec<PathBuf> {
+    (1..=BACKUP_COUNT)
+        .map(|generation| backup_path(path, generation))
+        .filter(|backup| backup.is_file())
+        .collect()
+}
+
+/// Replace the contents of the file without ever leaving it partially written. The data is written
+/// to a temporary file and flushed to disk before being renamed over the original, which is first
+/// copied to the newest of `backup_count` backups. Older backups are shifted along and the oldest is
+/// dropped.
+pub fn write_atomic(path: &Path, data: &[u8], backup_count: usize) -> io::Result<()> {
+    let temp_path = with_suffix(path, ".tmp");
+    {
+        let mut temp_file = File::create(&temp_path)?;
+        temp_file.write_all(data)?;
+        temp_file.sync_all()?;
+    }
+    if backup_count > 0 && path.is_file() {
+        rotate_backups(path, backup_count)?;
+    }
+    fs::rename(&temp_path, path)?;
+    sync_parent_directory(path)
+}
+
+/// Shift every backup back a generation and copy the file into the newest one.
+fn rotate_backups(path: &Path, backup_count: usize) -> io::Result<()> {
+    for generation in (1..backup_count).rev() {
+        let backup = backup_path(path, generation);
+        if backup.is_file() {
+            fs::rename(&backup, backup_path(path, generation + 1))?;
+        }
+    }
+    // Copying rather than renaming means the original stays in place until the new version is
+    // renamed over it.
+    let newest_backup = backup_path(path, 1);
+    fs::copy(path, &newest_backup)?;
+    File::open(&newest_backup)?.sync_all()
+}
+
+/// Make sure the rename itself has reached the disk. Directories can't be opened for syncing on
+/// every platform, so failing to open one isn't treated as an error.
+fn sync_parent_directory(path: &Path) -> io::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    match File::open(parent) {
+        Ok(directory) => directory.sync_all().or(Ok(())),
+        Err(_) => Ok(()),
+    }
+}
+
+/// An empty directory of its own for each test that works with files.
+#[cfg(test)]
+pub fn scratch_directory(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("text-game-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_write_keeps_the_previous_versions_as_backups() {
+        let directory = scratch_directory("backup-rotation");
+        let path = directory.join("map.json");
+        for version in 1..=4 {
+            write_atomic(&path, format!("version {}", version).as_bytes(), 2).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "version 4");
+        assert_eq!(fs::read_to_string(backup_path(&path, 1)).unwrap(), "version 3");
+        assert_eq!(fs::read_to_string(backup_path(&path, 2)).unwrap(), "version 2");
+        assert!(!backup_path(&path, 3).exists());
+        assert!(!with_suffix(&path, ".tmp").exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn backups_are_listed_from_the_most_recent() {
+        let directory = scratch_directory("backup-listing");
+        let path = directory.join("map.json");
+        assert!(backups(&path).is_empty());
+        for version in 1..=3 {
+            write_atomic(&path, format!("version {}", version).as_bytes(), BACKUP_COUNT).unwrap();
+        }
+        assert_eq!(backups(&path), vec![backup_path(&path, 1), backup_path(&path, 2)]);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn nothing_is_backed_up_when_backups_are_turned_off() {
+        let directory = scratch_directory("no-backups");
+        let path = directory.join("map.json");
+        write_atomic(&path, b"version 1", 0).unwrap();
+        write_atomic(&path, b"version 2", 0).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "version 2");
+        assert!(backups(&path).is_empty());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
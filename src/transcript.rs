@@ -0,0 +1,247 @@
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+
+use crate::console::Console;
+
+/// Starts each line of input in a written transcript.
+const INPUT_MARKER: char = '>';
+/// Starts a line of output that would otherwise be read as input.
+const ESCAPE: char = '\\';
+/// How many unchanged lines are shown around each difference.
+const DIFF_CONTEXT: usize = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Entry {
+    Input(String),
+    Output(String),
+}
+
+/// Everything that was read and written while playing, in order.
+///
+/// Transcripts are written as text, with each line of input starting with "> " and the output as it
+/// was shown. Output lines that start with ">" or "\" have a "\" added to the front. Trailing
+/// whitespace isn't kept, so a transcript can be edited by hand without that changing it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Transcript {
+    entries: Vec<Entry>,
+}
+
+impl Transcript {
+    pub fn new() -> Self {
+        Transcript::default()
+    }
+
+    /// Read a transcript that was written with `to_string`.
+    pub fn parse(text: &str) -> Self {
+        let mut transcript = Transcript::new();
+        for line in text.lines() {
+            match line.strip_prefix(INPUT_MARKER) {
+                Some(input) => transcript.record_input(input.strip_prefix(' ').unwrap_or(input)),
+                None => {
+                    transcript.record_output(line.strip_prefix(ESCAPE).unwrap_or(line));
+                    transcript.record_output("\n");
+                }
+            }
+        }
+        transcript
+    }
+
+    /// Every line of input, in order.
+    pub fn inputs(&self) -> Vec<String> {
+        self.entries.iter()
+            .filter_map(|entry| match entry {
+                Entry::Input(input) => Some(input.clone()),
+                Entry::Output(_) => None,
+            })
+            .collect()
+    }
+
+    fn record_input(&mut self, input: &str) {
+        self.entries.push(Entry::Input(input.to_string()));
+    }
+
+    fn record_output(&mut self, output: &str) {
+        // Output is written in pieces, so it's joined up to keep whole lines together.
+        match self.entries.last_mut() {
+            Some(Entry::Output(text)) => text.push_str(output),
+            _ => self.entries.push(Entry::Output(output.to_string())),
+        }
+    }
+}
+
+impl Display for Transcript {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for entry in &self.entries {
+            match entry {
+                Entry::Input(input) => writeln!(f, "{}", format!("{} {}", INPUT_MARKER, input).trim_end())?,
+                Entry::Output(output) => {
+                    for line in output.lines().map(str::trim_end) {
+                        if line.starts_with(INPUT_MARKER) || line.starts_with(ESCAPE) {
+                            write!(f, "{}", ESCAPE)?;
+                        }
+                        writeln!(f, "{}", line)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Records everything that's read from and written to another Console.
+pub struct Recorder<C: Console> {
+    console: C,
+    transcript: Transcript,
+}
+
+impl<C: Console> Recorder<C> {
+    pub fn new(console: C) -> Self {
+        Recorder { console, transcript: Transcript::new() }
+    }
+
+    pub fn transcript(&self) -> &Transcript {
+        &self.transcript
+    }
+}
+
+impl<C: Console> Console for Recorder<C> {
+    fn read_line(&mut self) -> Option<String> {
+        let line = self.console.read_line();
+        if let Some(line) = &line {
+            self.transcript.record_input(line);
+        }
+        line
+    }
+
+    fn write(&mut self, text: &str) {
+        self.transcript.record_output(text);
+        self.console.write(text);
+    }
+}
+
+/// Reads the provided lines of input, one at a time, and throws away everything that's written.
+struct Script {
+    input: VecDeque<String>,
+}
+
+impl Console for Script {
+    fn read_line(&mut self) -> Option<String> {
+        self.input.pop_front()
+    }
+
+    fn write(&mut self, _text: &str) {}
+}
+
+/// Feed the input from the expected transcript to `play`, returning the transcript of what actually
+/// happened.
+pub fn replay(expected: &Transcript, play: impl FnOnce(&mut dyn Console)) -> Transcript {
+    let mut recorder = Recorder::new(Script { input: expected.inputs().into() });
+    play(&mut recorder);
+    recorder.transcript
+}
+
+/// The lines that differ between two pieces of text, with a few unchanged lines around them, or None
+/// when they're the same. Removed lines start with "-" and added lines with "+".
+pub fn diff(expected: &str, actual: &str) -> Option<String> {
+    let expected = expected.lines().collect::<Vec<&str>>();
+    let actual = actual.lines().collect::<Vec<&str>>();
+    // The length of the longest common subsequence of the remaining lines, from each pair of
+    // positions to the end.
+    let mut common = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            lines.push((' ', expected[i]));
+            i += 1;
+            j += 1;
+        } else if j == actual.len() || (i < expected.len() && common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(('-', expected[i]));
+            i += 1;
+        } else {
+            lines.push(('+', actual[j]));
+            j += 1;
+        }
+    }
+
+    let changed = lines.iter()
+        .enumerate()
+        .filter(|(_, (change, _))| *change != ' ')
+        .map(|(idx, _)| idx)
+        .collect::<Vec<usize>>();
+    if changed.is_empty() {
+        return None;
+    }
+    let mut differences = String::new();
+    let mut shown_up_to = 0;
+    for (idx, (change, line)) in lines.iter().enumerate() {
+        let near_a_change = changed.iter().any(|changed_idx| changed_idx.abs_diff(idx) <= DIFF_CONTEXT);
+        if !near_a_change {
+            continue;
+        }
+        if idx > shown_up_to {
+            differences.push_str("...\n");
+        }
+        differences.push_str(&format!("{}{}\n", change, line));
+        shown_up_to = idx + 1;
+    }
+    if shown_up_to < lines.len() {
+        differences.push_str("...\n");
+    }
+    Some(differences)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(lines: &[&str]) -> Transcript {
+        let mut transcript = Transcript::new();
+        for line in lines {
+            match line.strip_prefix("IN:") {
+                Some(input) => transcript.record_input(input),
+                None => transcript.record_output(line),
+            }
+        }
+        transcript
+    }
+
+    #[test]
+    fn transcripts_can_be_written_and_read_back() {
+        let transcript = record(&["Current Location: Hall\n", "> a quote\n", "What do you want to do? ", "IN:north", "\\o/\n"]);
+        let text = transcript.to_string();
+        assert_eq!(text, "Current Location: Hall\n\\> a quote\nWhat do you want to do?\n> north\n\\\\o/\n");
+        assert_eq!(Transcript::parse(&text).to_string(), text);
+        assert_eq!(Transcript::parse(&text).inputs(), vec!["north".to_string()]);
+    }
+
+    #[test]
+    fn replaying_feeds_the_input_and_records_the_output() {
+        let expected = record(&["Which way? ", "IN:north", "You go north.\n", "Which way? ", "IN:", "Bye.\n"]);
+        let actual = replay(&expected, |console| {
+            while let Some(input) = console.read_line() {
+                writeln!(console, "Which way? {}", input);
+            }
+        });
+        assert_eq!(actual.to_string(), "> north\nWhich way? north\n>\nWhich way?\n");
+    }
+
+    #[test]
+    fn diffs_show_the_changed_lines() {
+        assert_eq!(diff("a\nb\n", "a\nb\n"), None);
+        let expected = "1\n2\n3\n4\n5\n6\n7\n";
+        let actual = "1\n2\n3\n4\nfive\n6\n7\n";
+        assert_eq!(diff(expected, actual), Some("...\n 3\n 4\n-5\n+five\n 6\n 7\n".to_string()));
+        assert_eq!(diff("a\n", "a\nb\n"), Some(" a\n+b\n".to_string()));
+    }
+}
@@ -0,0 +1,260 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// A directory of its own for each test, removed when the test finishes.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("text-game-cli-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    fn file(&self, name: &str) -> String {
+        self.0.join(name).to_str().unwrap().to_string()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_text-game")).args(args).output().unwrap()
+}
+
+fn run_with_input(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_text-game"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+/// Build a map with a hall and a kitchen to the north of it.
+fn build_house(dir: &TempDir) -> String {
+    let map = dir.file("house.json");
+    assert!(run(&["new", &map, "--name", "Hall", "--description", "A dusty hall.", "--title", "The House"]).status.success());
+    assert!(run(&["add-location", &map, "Kitchen", "--description", "A cramped kitchen."]).status.success());
+    assert!(run(&["connect", &map, "hall", "n", "Kitchen", "--both-ways"]).status.success());
+    map
+}
+
+#[test]
+fn maps_can_be_built_and_listed() {
+    let dir = TempDir::new("build");
+    let map = build_house(&dir);
+    let output = run(&["list", &map]);
+    assert!(output.status.success());
+    let listing = stdout(&output);
+    assert!(listing.contains("Hall (start)"), "{}", listing);
+    assert!(listing.contains("    north -> Kitchen"), "{}", listing);
+    assert!(listing.contains("    south -> Hall"), "{}", listing);
+    assert!(run(&["validate", &map]).status.success());
+}
+
+#[test]
+fn locations_can_be_described_and_changed() {
+    let dir = TempDir::new("describe");
+    let map = build_house(&dir);
+    let output = run(&["describe", &map, "Kitchen", "--name", "Scullery", "--tag", "ground floor", "--property", "smell=bread"]);
+    assert!(output.status.success());
+    let description = stdout(&run(&["describe", &map, "scullery"]));
+    assert!(description.contains("Name: Scullery"), "{}", description);
+    assert!(description.contains("Description: A cramped kitchen."), "{}", description);
+    assert!(description.contains("Tags: ground floor"), "{}", description);
+    assert!(description.contains("Property: smell = bread"), "{}", description);
+}
+
+#[test]
+fn maps_can_be_laid_out_from_their_directions() {
+    let dir = TempDir::new("layout");
+    let map = build_house(&dir);
+    assert!(run(&["describe", &map, "Hall", "--position", "2, 3"]).status.success());
+    let output = run(&["layout", &map]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "Every location has been laid out.\n");
+    let description = stdout(&run(&["describe", &map, "Kitchen"]));
+    assert!(description.contains("Position: 2, 4"), "{}", description);
+    assert_eq!(run(&["describe", &map, "Kitchen", "--position", "north"]).status.code(), Some(2));
+}
+
+#[test]
+fn a_separate_way_back_can_be_given() {
+    let dir = TempDir::new("back");
+    let map = build_house(&dir);
+    assert!(run(&["add-location", &map, "Mirror"]).status.success());
+    assert!(run(&["connect", &map, "Hall", "through the mirror", "Mirror", "--back", "step out"]).status.success());
+    let listing = stdout(&run(&["list", &map]));
+    assert!(listing.contains("    through the mirror -> Mirror"), "{}", listing);
+    assert!(listing.contains("    step out -> Hall"), "{}", listing);
+}
+
+#[test]
+fn maps_can_be_played_from_standard_input() {
+    let dir = TempDir::new("play");
+    let map = build_house(&dir);
+    let output = run_with_input(&["play", &map], "north\nquit\n");
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Description: A cramped kitchen."), "{}", stdout(&output));
+}
+
+#[test]
+fn maps_can_be_exported() {
+    let dir = TempDir::new("dot");
+    let map = build_house(&dir);
+    assert!(run(&["describe", &map, "Kitchen", "--tag", "region:Ground Floor"]).status.success());
+    let dot_file = dir.file("house.dot");
+    assert!(run(&["export", &map, &dot_file, "--highlight-root", "--collapse-both-ways", "--cluster-regions"]).status.success());
+    let dot = fs::read_to_string(&dot_file).unwrap();
+    assert!(dot.starts_with("digraph map {\n    label=\"The House\";\n"), "{}", dot);
+    assert!(dot.contains("[label=\"Hall\", peripheries=2];"), "{}", dot);
+    assert!(dot.contains("        label=\"Ground Floor\";\n        \""), "{}", dot);
+    assert!(dot.contains("[label=\"north / south\", dir=none];"), "{}", dot);
+
+    let mermaid_file = dir.file("house.mmd");
+    assert!(run(&["export", &map, &mermaid_file]).status.success());
+    let mermaid = fs::read_to_string(&mermaid_file).unwrap();
+    assert!(mermaid.contains("flowchart TD\n    n0[\"Hall\"]\n    n1[\"Kitchen\"]\n    n0 -->|\"north\"| n1\n"), "{}", mermaid);
+    let graphml_file = dir.file("house.graphml");
+    assert!(run(&["export", &map, &graphml_file]).status.success());
+    assert!(fs::read_to_string(&graphml_file).unwrap().contains("<data key=\"edge_label\">south</data>"));
+    let output = run(&["export", &map, &dir.file("house.twee")]);
+    assert!(output.status.success());
+    let warnings = String::from_utf8_lossy(&output.stderr);
+    assert!(warnings.contains("the region:Ground Floor tag on Kitchen is written as region:Ground-Floor."), "{}", warnings);
+    let html_file = dir.file("house.html");
+    assert!(run(&["export", &map, &html_file]).status.success());
+    let html = fs::read_to_string(&html_file).unwrap();
+    assert!(html.starts_with("<!DOCTYPE html>\n"), "{}", html);
+    assert!(html.contains(r#"{"name":"Kitchen","description":"A cramped kitchen.","exits":[{"direction":"south","to":0,"aliases":["south","s"]}]}"#), "{}", html);
+    assert_eq!(run(&["export", &map, &dir.file("house.png")]).status.code(), Some(2));
+}
+
+#[test]
+fn twine_stories_can_be_imported_and_exported_again() {
+    let dir = TempDir::new("twee");
+    let story = r#":: StoryTitle
+The House
+
+:: StoryData
+{
+  "ifid": "D674C58C-DEFA-4F70-B7A2-27742230C0FC",
+  "start": "Hall"
+}
+
+:: Hall [dusty indoors]
+A dusty hall.
+
+[[north->Kitchen]]
+
+:: Kitchen
+A cramped kitchen.
+
+[[south->Hall]]
+
+"#;
+    let twee_file = dir.file("house.twee");
+    fs::write(&twee_file, story).unwrap();
+    let map = dir.file("house.json");
+    let output = run(&["import", &twee_file, &map]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "d674c58c-defa-4f70-b7a2-27742230c0fc\n");
+    let description = stdout(&run(&["describe", &map, "Hall"]));
+    assert!(description.contains("Tags: dusty, indoors"), "{}", description);
+
+    let exported_file = dir.file("exported.tw");
+    assert!(run(&["export", &map, &exported_file]).status.success());
+    assert_eq!(fs::read_to_string(&exported_file).unwrap(), story);
+    assert_eq!(run(&["import", &twee_file, &map]).status.code(), Some(1));
+}
+
+#[test]
+fn recorded_transcripts_fail_to_replay_when_the_map_changes() {
+    let dir = TempDir::new("replay");
+    let map = build_house(&dir);
+    let transcript = dir.file("walkthrough.txt");
+    assert!(run_with_input(&["play", &map, "--record", &transcript], "north\nlook\n").status.success());
+    let recorded = fs::read_to_string(&transcript).unwrap();
+    assert!(recorded.contains("> north\n\nCurrent Location: Kitchen"), "{}", recorded);
+    assert!(recorded.contains("Description: A cramped kitchen."), "{}", recorded);
+
+    let output = run(&["replay", &map, &transcript]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(run(&["describe", &map, "Kitchen", "--description", "A spotless kitchen."]).status.success());
+    let output = run(&["replay", &map, &transcript]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("-    Description: A cramped kitchen.\n+    Description: A spotless kitchen."), "{}", stdout(&output));
+}
+
+#[test]
+fn failures_exit_with_1() {
+    let dir = TempDir::new("failures");
+    let map = build_house(&dir);
+    assert_eq!(run(&["new", &map]).status.code(), Some(1));
+    assert_eq!(run(&["connect", &map, "Hall", "east", "Cellar"]).status.code(), Some(1));
+    assert_eq!(run(&["list", &dir.file("missing.json")]).status.code(), Some(1));
+    assert_eq!(run(&["connect", &map, "Hall", "through", "Kitchen", "--both-ways"]).status.code(), Some(1));
+}
+
+#[test]
+fn maps_with_errors_fail_validation() {
+    let dir = TempDir::new("invalid");
+    let map = dir.file("broken.json");
+    fs::write(&map, r#"{"format_version": 3, "root_node_id": "a", "current_node_id": "a", "metadata": {},
+        "nodes": [{"id": "a", "element": {"name": "Hall", "description": ""}, "edges": {"north": "missing"}}]}"#).unwrap();
+    let output = run(&["validate", &map]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("Error:"), "{}", stdout(&output));
+}
+
+#[test]
+fn maps_are_only_saved_from_the_menus_when_they_change() {
+    let dir = TempDir::new("unchanged");
+    let map = build_house(&dir);
+    let backup = format!("{}.bak.1", map);
+    let saved = fs::read_to_string(&map).unwrap();
+    let backed_up = fs::read_to_string(&backup).unwrap();
+    // Undoing with nothing to undo doesn't change the map.
+    assert!(run_with_input(&[], &format!("2\n{}\n12\nx\nx\n", map)).status.success());
+    assert_eq!(fs::read_to_string(&map).unwrap(), saved);
+    assert_eq!(fs::read_to_string(&backup).unwrap(), backed_up);
+}
+
+#[test]
+fn broken_maps_can_be_recovered_from_a_backup() {
+    let dir = TempDir::new("recover");
+    let map = build_house(&dir);
+    fs::write(&map, "{ not a map").unwrap();
+    let output = run_with_input(&[], &format!("2\n{}\nb\ny\n12\nx\nx\n", map));
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Recovered from the backup."), "{}", stdout(&output));
+    // The newest backup was made before the hall was connected to the kitchen, and the recovered
+    // map replaces the broken one the next time it's saved.
+    let listing = stdout(&run(&["list", &map]));
+    assert!(listing.contains("Kitchen"), "{}", listing);
+    assert!(!listing.contains("north -> Kitchen"), "{}", listing);
+}
+
+#[test]
+fn misuse_exits_with_2() {
+    assert_eq!(run(&["explode"]).status.code(), Some(2));
+    assert_eq!(run(&["connect", "map.json", "Hall"]).status.code(), Some(2));
+    assert_eq!(run(&["list", "map.json", "--verbose"]).status.code(), Some(2));
+    assert_eq!(run(&["new", "map.json", "--name"]).status.code(), Some(2));
+    assert!(run(&["help"]).status.success());
+}